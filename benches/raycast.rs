@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::time::Duration;
+
+use raycaster::plugins::canvas::{upload_canvas, PixelCanvas};
+use raycaster::plugins::map::GameMap;
+use raycaster::plugins::math::Vec2f;
+use raycaster::plugins::player::PlayerView;
+use raycaster::plugins::raycast::{cast_ray, render_frame, FrameStyle, RaycastSettings, RenderStats};
+
+const MAP_SIZES: &[usize] = &[24, 64, 256, 1024];
+const RESOLUTIONS: &[(u32, u32)] = &[(320, 240), (400, 300), (800, 600), (1280, 720)];
+const RAY_DIRECTIONS: usize = 360;
+
+// Walled border with a regular grid of pillars, so rays travel a mix of
+// short and long distances whatever the map size
+fn bench_map(size: usize) -> GameMap {
+    let mut map = GameMap::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            let border = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+            let pillar = x % 6 == 3 && y % 6 == 3;
+            if border || pillar {
+                map.set_tile(x, y, (1 + (x + y) % 5) as u8);
+            }
+        }
+    }
+    map
+}
+
+fn bench_view(map: &GameMap) -> PlayerView {
+    PlayerView {
+        position: Vec2f::new(map.width as f32 / 2.0 + 0.5, map.height as f32 / 2.0 + 0.5),
+        ..default()
+    }
+}
+
+fn cast_ray_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cast_ray");
+    group.throughput(Throughput::Elements(RAY_DIRECTIONS as u64));
+
+    let directions: Vec<Vec2f> = (0..RAY_DIRECTIONS)
+        .map(|i| Vec2f::from_angle(i as f32 / RAY_DIRECTIONS as f32 * std::f32::consts::TAU))
+        .collect();
+
+    let max_distance = RaycastSettings::default().max_distance;
+    for &size in MAP_SIZES {
+        let map = bench_map(size);
+        let start = bench_view(&map).position;
+        group.bench_with_input(BenchmarkId::new("directions", size), &map, |b, map| {
+            b.iter(|| {
+                for &direction in &directions {
+                    black_box(cast_ray(&start, direction, map, max_distance));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn render_frame_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_3d_view");
+    let map = bench_map(24);
+    let view = bench_view(&map);
+    let settings = RaycastSettings::default();
+    let mut stats = RenderStats::default();
+
+    for &(width, height) in RESOLUTIONS {
+        let mut canvas = PixelCanvas::new(width, height);
+        group.throughput(Throughput::Elements((width * height) as u64));
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", width, height)), |b| {
+            b.iter(|| render_frame(&mut canvas, &view, &map, &settings, FrameStyle::default(), &mut stats));
+        });
+    }
+    group.finish();
+}
+
+fn upload_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("canvas_upload");
+
+    for &(width, height) in RESOLUTIONS {
+        let mut canvas = PixelCanvas::new(width, height);
+        let mut image = Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            canvas.pixels.clone(),
+            TextureFormat::Rgba8UnormSrgb,
+            default(),
+        );
+        group.throughput(Throughput::Bytes(canvas.pixels.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", width, height)), |b| {
+            b.iter(|| upload_canvas(&mut canvas, &mut image));
+        });
+    }
+    group.finish();
+}
+
+// Changes inside the noise threshold are reported as "no change"; anything
+// past it at the significance level is flagged as a regression or improvement
+fn config() -> Criterion {
+    Criterion::default()
+        .noise_threshold(0.05)
+        .significance_level(0.01)
+        .measurement_time(Duration::from_secs(3))
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = cast_ray_benches, render_frame_benches, upload_benches
+}
+criterion_main!(benches);
@@ -1,7 +1,6 @@
 use bevy::prelude::*;
 
-mod plugins;
-use plugins::{
+use raycaster::plugins::{
     window::{WindowPlugin as RaycasterWindowPlugin, WINDOW_WIDTH, WINDOW_HEIGHT, WINDOW_TITLE},
     canvas::CanvasPlugin,
     input::InputPlugin as RaycasterInputPlugin,
@@ -10,6 +9,18 @@ use plugins::{
     player::PlayerPlugin,
     map::MapPlugin,
     raycast::RaycastPlugin,
+    gamepad::GamepadPlugin,
+    actions::ActionsPlugin,
+    replay::ReplayPlugin,
+    save::SavePlugin,
+    hud::HudPlugin,
+    render::RenderPlugin,
+    raydebug::RayDebugPlugin,
+    lighting::LightingPlugin,
+    sky::SkyPlugin,
+    palette::PalettePlugin,
+    postprocess::PostProcessPlugin,
+    surface::SurfacePlugin,
 };
 
 fn main() {
@@ -25,13 +36,28 @@ fn main() {
         }))
         .add_plugins((
             RaycasterWindowPlugin,
+            ActionsPlugin,
             MathPlugin,
             MapPlugin,
             PlayerPlugin,
             CanvasPlugin,
             RaycastPlugin,
+            RenderPlugin,
             RaycasterInputPlugin,
+            GamepadPlugin,
+            ReplayPlugin,
+            SavePlugin,
+            HudPlugin,
             DebugPlugin,
+            RayDebugPlugin,
+        ))
+        // Bevy takes at most 15 plugins per tuple
+        .add_plugins((
+            LightingPlugin,
+            SkyPlugin,
+            PalettePlugin,
+            PostProcessPlugin,
+            SurfacePlugin,
         ))
         .run();
 }
\ No newline at end of file
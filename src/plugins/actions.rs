@@ -0,0 +1,554 @@
+use bevy::prelude::*;
+use bevy::input::InputSystem;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+
+pub const CONTROLS_CONFIG_PATH: &str = "controls.cfg";
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(load_input_map(CONTROLS_CONFIG_PATH))
+            .insert_resource(ActionState::default())
+            .add_systems(PreUpdate, update_action_state.in_set(ActionSystem).after(InputSystem));
+    }
+}
+
+// Runs in PreUpdate; systems reading ActionState there must run after it
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionSystem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    Use,
+    Fire,
+    Jump,
+    Crouch,
+    Sprint,
+    LimitPitch,
+    CaptureMouse,
+    ReleaseMouse,
+    ToggleMinimap,
+    ShowHelp,
+    ShowPlayerInfo,
+    RandomPixels,
+    ClearCanvas,
+    RedPattern,
+    GreenPattern,
+    BluePattern,
+    ToggleRecording,
+    PlayReplay,
+    QuickSave,
+    QuickLoad,
+    CycleSaveSlot,
+    ToggleDebugOverlay,
+    NextDebugPage,
+    ToggleRayView,
+    RayViewPrevColumn,
+    RayViewNextColumn,
+    RayViewStep,
+    RayViewStepBack,
+    TogglePaletteMode,
+    NextPostEffect,
+    TogglePostEffect,
+    PostEffectLess,
+    PostEffectMore,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 40] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Use,
+        Action::Fire,
+        Action::Jump,
+        Action::Crouch,
+        Action::Sprint,
+        Action::LimitPitch,
+        Action::CaptureMouse,
+        Action::ReleaseMouse,
+        Action::ToggleMinimap,
+        Action::ShowHelp,
+        Action::ShowPlayerInfo,
+        Action::RandomPixels,
+        Action::ClearCanvas,
+        Action::RedPattern,
+        Action::GreenPattern,
+        Action::BluePattern,
+        Action::ToggleRecording,
+        Action::PlayReplay,
+        Action::QuickSave,
+        Action::QuickLoad,
+        Action::CycleSaveSlot,
+        Action::ToggleDebugOverlay,
+        Action::NextDebugPage,
+        Action::ToggleRayView,
+        Action::RayViewPrevColumn,
+        Action::RayViewNextColumn,
+        Action::RayViewStep,
+        Action::RayViewStepBack,
+        Action::TogglePaletteMode,
+        Action::NextPostEffect,
+        Action::TogglePostEffect,
+        Action::PostEffectLess,
+        Action::PostEffectMore,
+        Action::Quit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::Use => "use",
+            Action::Fire => "fire",
+            Action::Jump => "jump",
+            Action::Crouch => "crouch",
+            Action::Sprint => "sprint",
+            Action::LimitPitch => "limit_pitch",
+            Action::CaptureMouse => "capture_mouse",
+            Action::ReleaseMouse => "release_mouse",
+            Action::ToggleMinimap => "toggle_minimap",
+            Action::ShowHelp => "show_help",
+            Action::ShowPlayerInfo => "show_player_info",
+            Action::RandomPixels => "random_pixels",
+            Action::ClearCanvas => "clear_canvas",
+            Action::RedPattern => "red_pattern",
+            Action::GreenPattern => "green_pattern",
+            Action::BluePattern => "blue_pattern",
+            Action::ToggleRecording => "toggle_recording",
+            Action::PlayReplay => "play_replay",
+            Action::QuickSave => "quick_save",
+            Action::QuickLoad => "quick_load",
+            Action::CycleSaveSlot => "cycle_save_slot",
+            Action::ToggleDebugOverlay => "toggle_debug_overlay",
+            Action::NextDebugPage => "next_debug_page",
+            Action::ToggleRayView => "toggle_ray_view",
+            Action::RayViewPrevColumn => "ray_view_prev_column",
+            Action::RayViewNextColumn => "ray_view_next_column",
+            Action::RayViewStep => "ray_view_step",
+            Action::RayViewStepBack => "ray_view_step_back",
+            Action::TogglePaletteMode => "toggle_palette_mode",
+            Action::NextPostEffect => "next_post_effect",
+            Action::TogglePostEffect => "toggle_post_effect",
+            Action::PostEffectLess => "post_effect_less",
+            Action::PostEffectMore => "post_effect_more",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| action.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("KeyA", KeyCode::KeyA), ("KeyB", KeyCode::KeyB), ("KeyC", KeyCode::KeyC),
+    ("KeyD", KeyCode::KeyD), ("KeyE", KeyCode::KeyE), ("KeyF", KeyCode::KeyF),
+    ("KeyG", KeyCode::KeyG), ("KeyH", KeyCode::KeyH), ("KeyI", KeyCode::KeyI),
+    ("KeyJ", KeyCode::KeyJ), ("KeyK", KeyCode::KeyK), ("KeyL", KeyCode::KeyL),
+    ("KeyM", KeyCode::KeyM), ("KeyN", KeyCode::KeyN), ("KeyO", KeyCode::KeyO),
+    ("KeyP", KeyCode::KeyP), ("KeyQ", KeyCode::KeyQ), ("KeyR", KeyCode::KeyR),
+    ("KeyS", KeyCode::KeyS), ("KeyT", KeyCode::KeyT), ("KeyU", KeyCode::KeyU),
+    ("KeyV", KeyCode::KeyV), ("KeyW", KeyCode::KeyW), ("KeyX", KeyCode::KeyX),
+    ("KeyY", KeyCode::KeyY), ("KeyZ", KeyCode::KeyZ),
+    ("Digit0", KeyCode::Digit0), ("Digit1", KeyCode::Digit1), ("Digit2", KeyCode::Digit2),
+    ("Digit3", KeyCode::Digit3), ("Digit4", KeyCode::Digit4), ("Digit5", KeyCode::Digit5),
+    ("Digit6", KeyCode::Digit6), ("Digit7", KeyCode::Digit7), ("Digit8", KeyCode::Digit8),
+    ("Digit9", KeyCode::Digit9),
+    ("F1", KeyCode::F1), ("F2", KeyCode::F2), ("F3", KeyCode::F3), ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5), ("F6", KeyCode::F6), ("F7", KeyCode::F7), ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9), ("F10", KeyCode::F10), ("F11", KeyCode::F11), ("F12", KeyCode::F12),
+    ("ArrowUp", KeyCode::ArrowUp), ("ArrowDown", KeyCode::ArrowDown),
+    ("ArrowLeft", KeyCode::ArrowLeft), ("ArrowRight", KeyCode::ArrowRight),
+    ("Space", KeyCode::Space), ("Enter", KeyCode::Enter), ("Escape", KeyCode::Escape),
+    ("Tab", KeyCode::Tab), ("Backspace", KeyCode::Backspace),
+    ("ShiftLeft", KeyCode::ShiftLeft), ("ShiftRight", KeyCode::ShiftRight),
+    ("ControlLeft", KeyCode::ControlLeft), ("ControlRight", KeyCode::ControlRight),
+    ("AltLeft", KeyCode::AltLeft), ("AltRight", KeyCode::AltRight),
+    ("Backquote", KeyCode::Backquote), ("Minus", KeyCode::Minus), ("Equal", KeyCode::Equal),
+    ("BracketLeft", KeyCode::BracketLeft), ("BracketRight", KeyCode::BracketRight),
+    ("Comma", KeyCode::Comma), ("Period", KeyCode::Period), ("Slash", KeyCode::Slash),
+];
+
+const MOUSE_NAMES: &[(&str, MouseButton)] = &[
+    ("Left", MouseButton::Left),
+    ("Right", MouseButton::Right),
+    ("Middle", MouseButton::Middle),
+    ("Back", MouseButton::Back),
+    ("Forward", MouseButton::Forward),
+];
+
+const GAMEPAD_NAMES: &[(&str, GamepadButton)] = &[
+    ("South", GamepadButton::South), ("East", GamepadButton::East),
+    ("North", GamepadButton::North), ("West", GamepadButton::West),
+    ("LeftTrigger", GamepadButton::LeftTrigger), ("LeftTrigger2", GamepadButton::LeftTrigger2),
+    ("RightTrigger", GamepadButton::RightTrigger), ("RightTrigger2", GamepadButton::RightTrigger2),
+    ("Select", GamepadButton::Select), ("Start", GamepadButton::Start),
+    ("LeftThumb", GamepadButton::LeftThumb), ("RightThumb", GamepadButton::RightThumb),
+    ("DPadUp", GamepadButton::DPadUp), ("DPadDown", GamepadButton::DPadDown),
+    ("DPadLeft", GamepadButton::DPadLeft), ("DPadRight", GamepadButton::DPadRight),
+];
+
+fn lookup_name<T: Copy + PartialEq>(table: &[(&'static str, T)], value: T) -> Option<&'static str> {
+    table.iter().find(|(_, v)| *v == value).map(|(name, _)| *name)
+}
+
+fn lookup_value<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|(n, _)| *n == name).map(|(_, value)| *value)
+}
+
+impl Binding {
+    // Config syntax: "KeyW", "Mouse:Left", "Pad:South"
+    pub fn parse(text: &str) -> Result<Binding, String> {
+        let text = text.trim();
+        if let Some(name) = text.strip_prefix("Mouse:") {
+            lookup_value(MOUSE_NAMES, name)
+                .map(Binding::Mouse)
+                .ok_or_else(|| format!("unknown mouse button '{}'", name))
+        } else if let Some(name) = text.strip_prefix("Pad:") {
+            lookup_value(GAMEPAD_NAMES, name)
+                .map(Binding::Gamepad)
+                .ok_or_else(|| format!("unknown gamepad button '{}'", name))
+        } else {
+            lookup_value(KEY_NAMES, text)
+                .map(Binding::Key)
+                .ok_or_else(|| format!("unknown key '{}'", text))
+        }
+    }
+
+    pub fn to_config_string(&self) -> String {
+        match self {
+            Binding::Key(key) => lookup_name(KEY_NAMES, *key)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{:?}", key)),
+            Binding::Mouse(button) => format!("Mouse:{}", lookup_name(MOUSE_NAMES, *button).unwrap_or("?")),
+            Binding::Gamepad(button) => format!("Pad:{}", lookup_name(GAMEPAD_NAMES, *button).unwrap_or("?")),
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct InputMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Binding::{Key, Mouse, Gamepad};
+
+        let mut map = Self { bindings: BTreeMap::new() };
+        map.bind(Action::MoveForward, &[Key(KeyCode::KeyW), Gamepad(GamepadButton::DPadUp)]);
+        map.bind(Action::MoveBackward, &[Key(KeyCode::KeyS), Gamepad(GamepadButton::DPadDown)]);
+        map.bind(Action::StrafeLeft, &[Key(KeyCode::KeyA), Gamepad(GamepadButton::DPadLeft)]);
+        map.bind(Action::StrafeRight, &[Key(KeyCode::KeyD), Gamepad(GamepadButton::DPadRight)]);
+        map.bind(Action::TurnLeft, &[Key(KeyCode::ArrowLeft)]);
+        map.bind(Action::TurnRight, &[Key(KeyCode::ArrowRight)]);
+        map.bind(Action::Use, &[Key(KeyCode::KeyE), Gamepad(GamepadButton::South)]);
+        map.bind(Action::Fire, &[Key(KeyCode::ControlLeft), Gamepad(GamepadButton::RightTrigger2)]);
+        map.bind(Action::Jump, &[Key(KeyCode::Space), Gamepad(GamepadButton::East)]);
+        map.bind(Action::Crouch, &[Key(KeyCode::KeyZ), Gamepad(GamepadButton::LeftThumb)]);
+        map.bind(Action::Sprint, &[Key(KeyCode::ShiftLeft), Gamepad(GamepadButton::LeftTrigger2)]);
+        map.bind(Action::LimitPitch, &[Key(KeyCode::KeyV)]);
+        map.bind(Action::CaptureMouse, &[Mouse(MouseButton::Left)]);
+        map.bind(Action::ReleaseMouse, &[Key(KeyCode::Escape)]);
+        map.bind(Action::ToggleMinimap, &[Key(KeyCode::KeyM), Gamepad(GamepadButton::Select)]);
+        map.bind(Action::ShowHelp, &[Key(KeyCode::F1), Gamepad(GamepadButton::Start)]);
+        map.bind(Action::ShowPlayerInfo, &[Key(KeyCode::KeyP)]);
+        map.bind(Action::RandomPixels, &[Key(KeyCode::KeyN)]);
+        map.bind(Action::ClearCanvas, &[Key(KeyCode::KeyC)]);
+        map.bind(Action::RedPattern, &[Key(KeyCode::KeyR)]);
+        map.bind(Action::GreenPattern, &[Key(KeyCode::KeyG)]);
+        map.bind(Action::BluePattern, &[Key(KeyCode::KeyB)]);
+        map.bind(Action::ToggleRecording, &[Key(KeyCode::F6)]);
+        map.bind(Action::PlayReplay, &[Key(KeyCode::F7)]);
+        map.bind(Action::QuickSave, &[Key(KeyCode::F5)]);
+        map.bind(Action::QuickLoad, &[Key(KeyCode::F9)]);
+        map.bind(Action::CycleSaveSlot, &[Key(KeyCode::F8)]);
+        map.bind(Action::ToggleDebugOverlay, &[Key(KeyCode::F3)]);
+        map.bind(Action::NextDebugPage, &[Key(KeyCode::F4)]);
+        map.bind(Action::ToggleRayView, &[Key(KeyCode::F2)]);
+        map.bind(Action::RayViewPrevColumn, &[Key(KeyCode::BracketLeft)]);
+        map.bind(Action::RayViewNextColumn, &[Key(KeyCode::BracketRight)]);
+        map.bind(Action::RayViewStep, &[Key(KeyCode::Period)]);
+        map.bind(Action::RayViewStepBack, &[Key(KeyCode::Comma)]);
+        map.bind(Action::TogglePaletteMode, &[Key(KeyCode::F11)]);
+        map.bind(Action::NextPostEffect, &[Key(KeyCode::F12)]);
+        map.bind(Action::TogglePostEffect, &[Key(KeyCode::Slash)]);
+        map.bind(Action::PostEffectLess, &[Key(KeyCode::Minus)]);
+        map.bind(Action::PostEffectMore, &[Key(KeyCode::Equal)]);
+        map.bind(Action::Quit, &[Key(KeyCode::F10)]);
+        map
+    }
+}
+
+impl InputMap {
+    pub fn bind(&mut self, action: Action, bindings: &[Binding]) {
+        let entry = self.bindings.entry(action).or_default();
+        for binding in bindings {
+            if !entry.contains(binding) {
+                entry.push(*binding);
+            }
+        }
+    }
+
+    pub fn bindings_for(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // Every binding that is shared by more than one action
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let mut owners: HashMap<Binding, Vec<Action>> = HashMap::new();
+        for (action, bindings) in &self.bindings {
+            for binding in bindings {
+                owners.entry(*binding).or_default().push(*action);
+            }
+        }
+
+        let mut conflicts: Vec<_> = owners.into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .collect();
+        conflicts.sort_by_key(|(_, actions)| actions[0]);
+        conflicts
+    }
+
+    // Format: one "action = binding, binding" per line, '#' starts a comment.
+    // Actions missing from the file keep their default bindings.
+    pub fn parse_config(text: &str) -> (InputMap, Vec<String>) {
+        let mut map = InputMap::default();
+        let mut warnings = Vec::new();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((name, values)) = line.split_once('=') else {
+                warnings.push(format!("line {}: expected 'action = bindings'", line_number + 1));
+                continue;
+            };
+
+            let Some(action) = Action::from_name(name.trim()) else {
+                warnings.push(format!("line {}: unknown action '{}'", line_number + 1, name.trim()));
+                continue;
+            };
+
+            let mut bindings = Vec::new();
+            for value in values.split(',').filter(|value| !value.trim().is_empty()) {
+                match Binding::parse(value) {
+                    Ok(binding) => bindings.push(binding),
+                    Err(message) => warnings.push(format!("line {}: {}", line_number + 1, message)),
+                }
+            }
+
+            map.bindings.insert(action, Vec::new());
+            map.bind(action, &bindings);
+        }
+
+        (map, warnings)
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut text = String::from(CONFIG_HEADER);
+        for action in Action::ALL {
+            let _ = writeln!(text, "{} = {}", action.name(), format_bindings(self.bindings_for(action)));
+        }
+        text
+    }
+
+    // What gets written on first launch: the defaults, commented out so
+    // they aren't pinned and later versions can change them
+    pub fn default_config() -> String {
+        let defaults = InputMap::default();
+        let mut text = String::from(CONFIG_HEADER);
+        text.push_str("# Uncomment a line to change it; commented lines follow the defaults\n\n");
+        for action in Action::ALL {
+            let _ = writeln!(text, "# {} = {}", action.name(), format_bindings(defaults.bindings_for(action)));
+        }
+        text
+    }
+}
+
+const CONFIG_HEADER: &str = "# Raycaster controls\n\
+    # Keys use Bevy KeyCode names, plus Mouse:<button> and Pad:<button>\n\
+    # Separate multiple bindings with commas\n\n";
+
+fn format_bindings(bindings: &[Binding]) -> String {
+    bindings.iter().map(Binding::to_config_string).collect::<Vec<_>>().join(", ")
+}
+
+fn load_input_map(path: &str) -> InputMap {
+    let map = match std::fs::read_to_string(path) {
+        Ok(text) => {
+            let (map, warnings) = InputMap::parse_config(&text);
+            for warning in &warnings {
+                warn!("{}: {}", path, warning);
+            }
+            info!("Controls loaded from {}", path);
+            map
+        }
+        Err(_) => {
+            let map = InputMap::default();
+            match std::fs::write(path, InputMap::default_config()) {
+                Ok(()) => info!("Default controls written to {}", path),
+                Err(err) => warn!("Could not write {}: {}", path, err),
+            }
+            map
+        }
+    };
+
+    for (binding, actions) in map.conflicts() {
+        let names: Vec<&str> = actions.iter().map(Action::name).collect();
+        warn!("Binding conflict: {} is bound to {}", binding.to_config_string(), names.join(", "));
+    }
+
+    map
+}
+
+#[derive(Resource, Default)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    just_released: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.just_released.contains(&action)
+    }
+}
+
+fn update_action_state(
+    input_map: Res<InputMap>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut state: ResMut<ActionState>,
+) {
+    let state = &mut *state;
+    let was_pressed = std::mem::take(&mut state.pressed);
+    state.just_pressed.clear();
+    state.just_released.clear();
+
+    for (action, bindings) in &input_map.bindings {
+        let pressed = bindings.iter().any(|binding| match binding {
+            Binding::Key(key) => keyboard_input.pressed(*key),
+            Binding::Mouse(button) => mouse_button.pressed(*button),
+            Binding::Gamepad(button) => gamepads.iter().any(|gamepad| gamepad.pressed(*button)),
+        });
+
+        if pressed {
+            state.pressed.insert(*action);
+            if !was_pressed.contains(action) {
+                state.just_pressed.insert(*action);
+            }
+        } else if was_pressed.contains(action) {
+            state.just_released.insert(*action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_parse() {
+        assert_eq!(Binding::parse("KeyW"), Ok(Binding::Key(KeyCode::KeyW)));
+        assert_eq!(Binding::parse(" Space "), Ok(Binding::Key(KeyCode::Space)));
+        assert_eq!(Binding::parse("Mouse:Left"), Ok(Binding::Mouse(MouseButton::Left)));
+        assert_eq!(Binding::parse("Pad:South"), Ok(Binding::Gamepad(GamepadButton::South)));
+        assert!(Binding::parse("KeyWW").is_err());
+        assert!(Binding::parse("Mouse:Thumb").is_err());
+        assert!(Binding::parse("Pad:KeyW").is_err());
+        assert!(Binding::parse("").is_err());
+
+        for text in ["KeyW", "Mouse:Left", "Pad:South", "F12", "Slash"] {
+            assert_eq!(Binding::parse(text).unwrap().to_config_string(), text);
+        }
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let mut map = InputMap::default();
+        map.bindings.insert(Action::Jump, Vec::new());
+        map.bind(Action::Jump, &[Binding::Mouse(MouseButton::Right), Binding::Gamepad(GamepadButton::North)]);
+        map.bindings.insert(Action::Fire, Vec::new());
+
+        let (parsed, warnings) = InputMap::parse_config(&map.to_config());
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(parsed.bindings, map.bindings);
+    }
+
+    #[test]
+    fn config_warnings_keep_defaults() {
+        let text = "jump = KeyJ, Nope\nnot_an_action = KeyK\nno equals sign\nmove_forward = KeyW # comment\n";
+        let (map, warnings) = InputMap::parse_config(text);
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert_eq!(map.bindings_for(Action::Jump), &[Binding::Key(KeyCode::KeyJ)]);
+        assert_eq!(map.bindings_for(Action::MoveForward), &[Binding::Key(KeyCode::KeyW)]);
+        assert_eq!(map.bindings_for(Action::Use), InputMap::default().bindings_for(Action::Use));
+    }
+
+    #[test]
+    fn default_config_leaves_defaults_unpinned() {
+        let (map, warnings) = InputMap::parse_config(&InputMap::default_config());
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(map.bindings, InputMap::default().bindings);
+
+        // An explicit binding stays explicit, even where it conflicts
+        let (map, warnings) = InputMap::parse_config("random_pixels = Space\n");
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(map.bindings_for(Action::RandomPixels), &[Binding::Key(KeyCode::Space)]);
+        assert_eq!(map.conflicts().len(), 1);
+    }
+
+    #[test]
+    fn conflicts_are_reported() {
+        assert!(InputMap::default().conflicts().is_empty());
+
+        let mut map = InputMap::default();
+        map.bind(Action::Fire, &[Binding::Key(KeyCode::Space)]);
+        map.bind(Action::Use, &[Binding::Key(KeyCode::Space)]);
+        let conflicts = map.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        let (binding, mut actions) = conflicts[0].clone();
+        actions.sort();
+        assert_eq!(binding, Binding::Key(KeyCode::Space));
+        let mut expected = vec![Action::Use, Action::Fire, Action::Jump];
+        expected.sort();
+        assert_eq!(actions, expected);
+    }
+}
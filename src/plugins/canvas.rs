@@ -9,16 +9,48 @@ pub struct CanvasPlugin;
 impl Plugin for CanvasPlugin {
     fn build(&self, app: &mut App) {
         app
+            .configure_sets(Update, (
+                CanvasPass::World,
+                CanvasPass::Resolve,
+                CanvasPass::Overlay,
+                CanvasPass::PostProcess,
+                CanvasPass::Upload,
+            ).chain())
             .add_systems(Startup, setup_canvas)
-            .add_systems(Update, update_canvas_display);
+            .add_systems(Update, update_canvas_display.in_set(CanvasPass::Upload));
     }
 }
 
+// Drawing order within a frame: the 3D view, then palette indices turned
+// into pixels, then anything on top of it, then filters over the finished
+// frame, then the copy to the GPU image
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CanvasPass {
+    World,
+    Resolve,
+    Overlay,
+    PostProcess,
+    Upload,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    Replace,  // Overwrite RGBA, ignoring alpha
+    #[default]
+    Normal,   // Alpha compositing ("over")
+    Additive,
+    Multiply,
+    Screen,
+}
+
 #[derive(Resource)]
 pub struct PixelCanvas {
     pub pixels: Vec<u8>,
     pub width: u32,
     pub height: u32,
+    pub blend_mode: BlendMode,
+    pub indices: Option<Vec<u8>>, // Palette mode: one palette index per pixel
+    indices_pending: bool,        // Indices drawn since the last resolve
 }
 
 impl PixelCanvas {
@@ -31,6 +63,9 @@ impl PixelCanvas {
             pixels: vec![0; pixel_count],
             width,
             height,
+            blend_mode: BlendMode::Normal,
+            indices: None,
+            indices_pending: false,
         }
     }
     
@@ -40,24 +75,124 @@ impl PixelCanvas {
         }
         
         let index = ((y * self.width + x) * 4) as usize;
-        self.pixels[index..index + 4].copy_from_slice(&color);
+        let pixel = &mut self.pixels[index..index + 4];
+        
+        // Fast path: opaque normal writes (e.g. walls) are a plain copy
+        if self.blend_mode == BlendMode::Replace || (color[3] == 255 && self.blend_mode == BlendMode::Normal) {
+            pixel.copy_from_slice(&color);
+            return;
+        }
+        
+        let dst = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        pixel.copy_from_slice(&blend_color(dst, color, self.blend_mode));
+    }
+    
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        if x >= self.width || y >= self.height {
+            return [0, 0, 0, 0];
+        }
+        
+        let index = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[index],
+            self.pixels[index + 1],
+            self.pixels[index + 2],
+            self.pixels[index + 3],
+        ]
+    }
+    
+    // Draws with a different blend mode, restoring the previous one after
+    pub fn with_blend_mode(&mut self, mode: BlendMode, draw: impl FnOnce(&mut Self)) {
+        let previous = self.blend_mode;
+        self.blend_mode = mode;
+        draw(self);
+        self.blend_mode = previous;
     }
     
+    // Also drops any indices not yet resolved: whatever is drawn next
+    // replaces them
     pub fn clear(&mut self, color: [u8; 4]) {
         for chunk in self.pixels.chunks_mut(4) {
             chunk.copy_from_slice(&color);
         }
+        self.indices_pending = false;
+    }
+    
+    pub fn set_indexed(&mut self, indexed: bool) {
+        self.indices = indexed.then(|| vec![0; (self.width * self.height) as usize]);
+        self.indices_pending = false;
+    }
+    
+    // Palette mode only; ignored while the canvas holds plain RGBA
+    pub fn set_index(&mut self, x: u32, y: u32, index: u8) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        if let Some(indices) = self.indices.as_mut() {
+            indices[(y * self.width + x) as usize] = index;
+            self.indices_pending = true;
+        }
+    }
+    
+    pub fn clear_indices(&mut self, index: u8) {
+        if let Some(indices) = self.indices.as_mut() {
+            indices.fill(index);
+            self.indices_pending = true;
+        }
+    }
+    
+    // Turns indices drawn since the last resolve into pixels
+    pub fn resolve_indices(&mut self, colors: &[[u8; 3]; 256]) {
+        let Some(indices) = self.indices.as_ref().filter(|_| self.indices_pending) else { return };
+        for (pixel, &index) in self.pixels.chunks_mut(4).zip(indices) {
+            let [r, g, b] = colors[index as usize];
+            pixel.copy_from_slice(&[r, g, b, 255]);
+        }
+        self.indices_pending = false;
     }
     
     pub fn draw_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 4]) {
-        for dy in 0..height {
-            for dx in 0..width {
-                self.set_pixel(x + dx, y + dy, color);
+        // Clip first; x + width can overflow u32 near the edge
+        let end_x = x.saturating_add(width).min(self.width);
+        let end_y = y.saturating_add(height).min(self.height);
+        
+        for py in y..end_y {
+            for px in x..end_x {
+                self.set_pixel(px, py, color);
             }
         }
     }
 }
 
+fn mul_255(a: u32, b: u32) -> u32 {
+    (a * b + 127) / 255
+}
+
+// Combines src over dst with the given mode, weighted by src alpha
+pub fn blend_color(dst: [u8; 4], src: [u8; 4], mode: BlendMode) -> [u8; 4] {
+    if mode == BlendMode::Replace {
+        return src;
+    }
+    
+    let alpha = src[3] as u32;
+    let mut result = [0u8; 4];
+    
+    for channel in 0..3 {
+        let s = src[channel] as u32;
+        let d = dst[channel] as u32;
+        let blended = match mode {
+            BlendMode::Replace | BlendMode::Normal => s,
+            BlendMode::Additive => (s + d).min(255),
+            BlendMode::Multiply => mul_255(s, d),
+            BlendMode::Screen => 255 - mul_255(255 - s, 255 - d),
+        };
+        result[channel] = (mul_255(blended, alpha) + mul_255(d, 255 - alpha)) as u8;
+    }
+    
+    result[3] = (alpha + mul_255(dst[3] as u32, 255 - alpha)) as u8;
+    result
+}
+
 #[derive(Component)]
 pub struct CanvasSprite;
 
@@ -72,6 +207,8 @@ fn setup_canvas(
     canvas.draw_rect(200, 100, 60, 60, [0, 255, 0, 255]);
     canvas.set_pixel(10, 10, [255, 255, 255, 255]);
     
+    // The image gets its own buffer once; from then on the two are swapped
+    // on upload instead of copied
     let image = Image::new(
         Extent3d {
             width: canvas.width,
@@ -79,7 +216,7 @@ fn setup_canvas(
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
-        canvas.pixels.clone(),
+        vec![0; canvas.pixels.len()],
         TextureFormat::Rgba8UnormSrgb,
         default(),
     );
@@ -101,14 +238,98 @@ fn setup_canvas(
 
 fn update_canvas_display(
     mut images: ResMut<Assets<Image>>,
-    canvas: Res<PixelCanvas>,
+    mut canvas: ResMut<PixelCanvas>,
     query: Query<&Sprite, With<CanvasSprite>>,
 ) {
     if canvas.is_changed() {
+        // Swapping buffers must not count as a change, or every frame would
+        // look dirty to the next one
+        let canvas = canvas.bypass_change_detection();
         for sprite in query.iter() {
             if let Some(image) = images.get_mut(&sprite.image) {
-                image.data = Some(canvas.pixels.clone());
+                upload_canvas(canvas, image);
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// Hands the finished frame to the image by swapping buffers instead of
+// cloning the canvas into it. Bevy still copies the image data when it
+// extracts the asset for rendering; only the main-world clone is gone.
+// The canvas is left holding the image's old buffer, i.e. the frame before
+// last; every frame is redrawn from a clear, so nothing depends on the
+// canvas keeping its contents across an upload
+pub fn upload_canvas(canvas: &mut PixelCanvas, image: &mut Image) {
+    match image.data.as_mut() {
+        Some(data) if data.len() == canvas.pixels.len() => std::mem::swap(data, &mut canvas.pixels),
+        _ => image.data = Some(canvas.pixels.clone()), // Size mismatch: fall back to a copy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DST: [u8; 4] = [100, 200, 50, 255];
+
+    fn src(alpha: u8) -> [u8; 4] {
+        [200, 100, 0, alpha]
+    }
+
+    #[test]
+    fn blend_modes_at_each_alpha() {
+        // Expected results for src(alpha) over DST at alpha 0, 128 and 255
+        let table = [
+            (BlendMode::Normal, [[100, 200, 50, 255], [150, 150, 25, 255], [200, 100, 0, 255]]),
+            (BlendMode::Additive, [[100, 200, 50, 255], [178, 228, 50, 255], [255, 255, 50, 255]]),
+            (BlendMode::Multiply, [[100, 200, 50, 255], [89, 139, 25, 255], [78, 78, 0, 255]]),
+            (BlendMode::Screen, [[100, 200, 50, 255], [161, 211, 50, 255], [222, 222, 50, 255]]),
+        ];
+
+        for (mode, expected) in table {
+            for (alpha, want) in [0, 128, 255].into_iter().zip(expected) {
+                assert_eq!(blend_color(DST, src(alpha), mode), want, "{mode:?} at alpha {alpha}");
+            }
+        }
+
+        for alpha in [0, 128, 255] {
+            assert_eq!(blend_color(DST, src(alpha), BlendMode::Replace), src(alpha));
+        }
+    }
+
+    #[test]
+    fn set_pixel_matches_blend_color() {
+        let destinations = [DST, [0, 0, 0, 0], [255, 255, 255, 255], [10, 20, 30, 128]];
+        let modes = [
+            BlendMode::Replace,
+            BlendMode::Normal,
+            BlendMode::Additive,
+            BlendMode::Multiply,
+            BlendMode::Screen,
+        ];
+
+        for dst in destinations {
+            for mode in modes {
+                for alpha in [0, 128, 255] {
+                    let mut canvas = PixelCanvas::new(1, 1);
+                    canvas.clear(dst);
+                    canvas.with_blend_mode(mode, |c| c.set_pixel(0, 0, src(alpha)));
+                    assert_eq!(
+                        canvas.get_pixel(0, 0),
+                        blend_color(dst, src(alpha), mode),
+                        "{mode:?} at alpha {alpha} over {dst:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn opaque_normal_writes_take_the_fast_path() {
+        let mut canvas = PixelCanvas::new(2, 1);
+        canvas.clear([10, 20, 30, 40]);
+        canvas.set_pixel(0, 0, src(255));
+        assert_eq!(canvas.get_pixel(0, 0), src(255));
+        assert_eq!(canvas.get_pixel(1, 0), [10, 20, 30, 40]);
+    }
+}
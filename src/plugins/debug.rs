@@ -1,5 +1,12 @@
 use bevy::prelude::*;
 use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use std::collections::VecDeque;
+use super::actions::{Action, ActionState};
+use super::canvas::{PixelCanvas, CanvasPass};
+use super::hud::HudFont;
+use super::text::TextStyle;
+
+const FRAME_HISTORY: usize = 120;
 
 pub struct DebugPlugin;
 
@@ -7,14 +14,49 @@ impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_plugins(FrameTimeDiagnosticsPlugin::default())
+            .init_resource::<DebugOverlay>()
+            .add_debug_page("Performance", performance_debug_page)
             .add_systems(Startup, setup_debug)
             .add_systems(Update, (
                 display_performance_info,
                 log_startup_complete,
+                handle_overlay_controls,
+                record_frame_time,
+                draw_debug_overlay.in_set(CanvasPass::Overlay),
             ));
     }
 }
 
+// A page returns the lines to show; it can read any resource
+pub type DebugPageFn = fn(&World) -> Vec<String>;
+
+struct DebugPage {
+    name: &'static str,
+    lines: DebugPageFn,
+}
+
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    pub current_page: usize,
+    pages: Vec<DebugPage>,
+    frame_times: VecDeque<f32>, // Milliseconds, oldest first
+}
+
+pub trait DebugOverlayAppExt {
+    fn add_debug_page(&mut self, name: &'static str, lines: DebugPageFn) -> &mut Self;
+}
+
+impl DebugOverlayAppExt for App {
+    fn add_debug_page(&mut self, name: &'static str, lines: DebugPageFn) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<DebugOverlay>()
+            .pages
+            .push(DebugPage { name, lines });
+        self
+    }
+}
+
 #[derive(Resource)]
 struct DebugState {
     startup_logged: bool,
@@ -30,6 +72,24 @@ impl Default for DebugState {
     }
 }
 
+fn performance_debug_page(world: &World) -> Vec<String> {
+    let overlay = world.resource::<DebugOverlay>();
+    let diagnostics = world.resource::<DiagnosticsStore>();
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or(0.0);
+    
+    let count = overlay.frame_times.len().max(1) as f32;
+    let average = overlay.frame_times.iter().sum::<f32>() / count;
+    let worst = overlay.frame_times.iter().copied().fold(0.0, f32::max);
+    
+    vec![
+        format!("FPS: {:.1}", fps),
+        format!("Frame: {:.2} ms avg, {:.2} ms worst", average, worst),
+    ]
+}
+
 fn setup_debug(mut commands: Commands) {
     commands.insert_resource(DebugState::default());
     info!("Debug system initialized");
@@ -75,9 +135,77 @@ fn log_startup_complete(
         info!("Next Phase: Vector Math & Line Drawing");
         
         info!("Try these controls:");
-        info!("   [Space] - Random pixels");
+        info!("   [Space/Z] - Jump / crouch");
+        info!("   [Shift] - Sprint");
+        info!("   [V] - Limit looking up and down");
+        info!("   [N] - Random pixels");
         info!("   [C] - Clear canvas");  
         info!("   [R/G/B] - Colored patterns");
         info!("   [F1] - Full help");
+        info!("   [F3/F4] - Debug overlay / next page");
+        info!("   [F10] - Quit");
+    }
+}
+
+fn handle_overlay_controls(
+    actions: Res<ActionState>,
+    mut overlay: ResMut<DebugOverlay>,
+) {
+    if actions.just_pressed(Action::ToggleDebugOverlay) {
+        overlay.visible = !overlay.visible;
+    }
+    
+    if actions.just_pressed(Action::NextDebugPage) && !overlay.pages.is_empty() {
+        overlay.visible = true;
+        overlay.current_page = (overlay.current_page + 1) % overlay.pages.len();
+    }
+}
+
+fn record_frame_time(
+    time: Res<Time>,
+    mut overlay: ResMut<DebugOverlay>,
+) {
+    overlay.frame_times.push_back(time.delta_secs() * 1000.0);
+    if overlay.frame_times.len() > FRAME_HISTORY {
+        overlay.frame_times.pop_front();
+    }
+}
+
+fn draw_debug_overlay(world: &mut World) {
+    let overlay = world.resource::<DebugOverlay>();
+    if !overlay.visible || overlay.pages.is_empty() {
+        return;
     }
-}
\ No newline at end of file
+    
+    let page_index = overlay.current_page.min(overlay.pages.len() - 1);
+    let page = &overlay.pages[page_index];
+    let title = format!("{{yellow}}[{}/{}] {}{{/}}", page_index + 1, overlay.pages.len(), page.name);
+    let lines = (page.lines)(world);
+    let frame_times: Vec<f32> = overlay.frame_times.iter().copied().collect();
+    
+    world.resource_scope(|world, mut canvas: Mut<PixelCanvas>| {
+        let font = &world.resource::<HudFont>().0;
+        let style = TextStyle::default();
+        let text = std::iter::once(title).chain(lines).collect::<Vec<_>>().join("\n");
+        let (text_width, text_height) = font.measure(&text, &style);
+        
+        let graph_height = 30;
+        let width = (text_width as i32).max(FRAME_HISTORY as i32) + 8;
+        let height = text_height as i32 + graph_height + 12;
+        let (x, y) = (4, 24);
+        
+        canvas.fill_rect(x, y, width, height, [0, 0, 0, 160]);
+        canvas.draw_text(font, &text, x + 4, y + 4, &style);
+        
+        // Rolling frame-time graph; the line marks 16.7 ms (60 FPS)
+        let graph_bottom = y + height - 4;
+        let scale = graph_height as f32 / 33.3;
+        let budget_y = graph_bottom - (16.7 * scale) as i32;
+        canvas.draw_span(x + 4, x + 4 + FRAME_HISTORY as i32, budget_y, [255, 255, 255, 80]);
+        for (i, ms) in frame_times.iter().enumerate() {
+            let bar = ((ms * scale) as i32).clamp(1, graph_height);
+            let color = if *ms > 16.7 { [255, 80, 80, 255] } else { [80, 255, 80, 255] };
+            canvas.fill_rect(x + 4 + i as i32, graph_bottom - bar, 1, bar, color);
+        }
+    });
+}
@@ -0,0 +1,598 @@
+use bevy::log::info;
+use std::path::Path;
+use super::canvas::PixelCanvas;
+
+// A standalone RGBA image that can be blitted onto the canvas
+#[derive(Clone)]
+pub struct PixelImage {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            pixels: vec![0; (width * height * 4) as usize],
+            width,
+            height,
+        }
+    }
+
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Option<Self> {
+        if Some(pixels.len()) != image_bytes(width, height, 4) {
+            return None;
+        }
+        Some(Self { pixels, width, height })
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        if x >= self.width || y >= self.height {
+            return [0, 0, 0, 0];
+        }
+
+        let index = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[index],
+            self.pixels[index + 1],
+            self.pixels[index + 2],
+            self.pixels[index + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let index = ((y * self.width + x) * 4) as usize;
+        self.pixels[index..index + 4].copy_from_slice(&color);
+    }
+}
+
+// Size of a width x height image, or None if it doesn't fit in memory
+// Offsets 0..=radius that put center + offset or center - offset within 0..size
+fn visible_offsets(center: i64, size: i64, radius: i64) -> std::ops::RangeInclusive<i64> {
+    let after = (-center).max(0)..=(size - 1 - center).min(radius);
+    let before = (center - size + 1).max(0)..=center.min(radius);
+    let (start, end) = match (after.is_empty(), before.is_empty()) {
+        (true, true) => return 1..=0,
+        (true, false) => (*before.start(), *before.end()),
+        (false, true) => (*after.start(), *after.end()),
+        // Both sides are within size of each other, so this adds at most that many
+        (false, false) => (*after.start().min(before.start()), *after.end().max(before.end())),
+    };
+    start..=end
+}
+
+fn image_bytes(width: u32, height: u32, channels: usize) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)?.checked_mul(channels)
+}
+
+// Names that end up in asset paths, e.g. a map's sky, come from save
+// files, so they must stay inside their asset directory
+pub fn is_asset_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+// Binary PPM (P6), the same format save thumbnails are written in
+pub fn load_ppm(path: &Path) -> Option<PixelImage> {
+    let data = std::fs::read(path).ok()?;
+    let mut fields = Vec::new();
+    let mut position = 0;
+    while fields.len() < 4 {
+        while data.get(position)?.is_ascii_whitespace() {
+            position += 1;
+        }
+        if data[position] == b'#' {
+            while *data.get(position)? != b'\n' {
+                position += 1;
+            }
+            continue;
+        }
+        let start = position;
+        while !data.get(position)?.is_ascii_whitespace() {
+            position += 1;
+        }
+        fields.push(std::str::from_utf8(&data[start..position]).ok()?.to_string());
+    }
+    position += 1; // Single whitespace before the pixels
+
+    let (width, height, max) = match fields.as_slice() {
+        [magic, width, height, max] if magic == "P6" => (width.parse().ok()?, height.parse().ok()?, max.parse::<u32>().ok()?),
+        _ => return None,
+    };
+    if max != 255 || width == 0 || height == 0 {
+        return None;
+    }
+
+    let rgb = data.get(position..position.checked_add(image_bytes(width, height, 3)?)?)?;
+    let pixels = rgb.chunks(3).flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]).collect();
+    let image = PixelImage::from_rgba(width, height, pixels)?;
+    info!("Image loaded from {}", path.display());
+    Some(image)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlitOptions {
+    pub source: Option<PixelRect>, // None = whole image
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub scale: f32,
+    pub color_key: Option<[u8; 4]>, // Source pixels of this color are skipped
+}
+
+impl Default for BlitOptions {
+    fn default() -> Self {
+        Self {
+            source: None,
+            flip_x: false,
+            flip_y: false,
+            scale: 1.0,
+            color_key: None,
+        }
+    }
+}
+
+// All primitives take signed coordinates and clip against the canvas, so
+// shapes that are partly or fully off-canvas are safe to draw
+impl PixelCanvas {
+    pub fn plot(&mut self, x: i32, y: i32, color: [u8; 4]) {
+        if x >= 0 && y >= 0 {
+            self.set_pixel(x as u32, y as u32, color);
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: [u8; 4]) {
+        let x0 = x.max(0) as i64;
+        let y0 = y.max(0) as i64;
+        let x1 = (x as i64 + width as i64).min(self.width as i64);
+        let y1 = (y as i64 + height as i64).min(self.height as i64);
+
+        for py in y0..y1 {
+            self.draw_span(x0 as i32, x1 as i32 - 1, py as i32, color);
+        }
+    }
+
+    pub fn draw_rect_outline(&mut self, x: i32, y: i32, width: i32, height: i32, color: [u8; 4]) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let right = x.saturating_add(width - 1);
+        let bottom = y.saturating_add(height - 1);
+        self.draw_span(x, right, y, color);
+        self.draw_span(x, right, bottom, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(right, y, 1, height, color);
+    }
+
+    // Horizontal run of pixels from x0 to x1 inclusive
+    pub fn draw_span(&mut self, x0: i32, x1: i32, y: i32, color: [u8; 4]) {
+        if y < 0 || y >= self.height as i32 {
+            return;
+        }
+
+        let (x0, x1) = if x0 <= x1 { (x0, x1) } else { (x1, x0) };
+        let start = x0.max(0);
+        let end = x1.min(self.width as i32 - 1);
+        for x in start..=end {
+            self.set_pixel(x as u32, y as u32, color);
+        }
+    }
+
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: [u8; 4]) {
+        let Some((x0, y0, x1, y1)) = self.clip_line(x0, y0, x1, y1) else { return };
+
+        // Bresenham, in i64 so the differences can't overflow
+        let (mut x0, mut y0, x1, y1) = (x0 as i64, y0 as i64, x1 as i64, y1 as i64);
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            self.plot(x0 as i32, y0 as i32, color);
+            if x0 == x1 && y0 == y1 {
+                break;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x0 += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y0 += step_y;
+            }
+        }
+    }
+
+    // Liang-Barsky clip against the canvas, so lines with far off-canvas
+    // endpoints don't walk millions of invisible pixels
+    fn clip_line(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> Option<(i32, i32, i32, i32)> {
+        let (x0f, y0f) = (x0 as f64, y0 as f64);
+        let (dx, dy) = (x1 as f64 - x0f, y1 as f64 - y0f);
+        let max_x = self.width as f64 - 1.0;
+        let max_y = self.height as f64 - 1.0;
+
+        let mut t0: f64 = 0.0;
+        let mut t1: f64 = 1.0;
+        for (p, q) in [(-dx, x0f), (dx, max_x - x0f), (-dy, y0f), (dy, max_y - y0f)] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else {
+                let r = q / p;
+                if p < 0.0 {
+                    t0 = t0.max(r);
+                } else {
+                    t1 = t1.min(r);
+                }
+            }
+        }
+
+        if t0 > t1 {
+            return None;
+        }
+
+        Some((
+            (x0f + t0 * dx).round() as i32,
+            (y0f + t0 * dy).round() as i32,
+            (x0f + t1 * dx).round() as i32,
+            (y0f + t1 * dy).round() as i32,
+        ))
+    }
+
+    pub fn draw_thick_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, thickness: f32, color: [u8; 4]) {
+        if thickness <= 1.0 {
+            self.draw_line(x0, y0, x1, y1, color);
+            return;
+        }
+
+        let (dx, dy) = ((x1 as i64 - x0 as i64) as f32, (y1 as i64 - y0 as i64) as f32);
+        let length = (dx * dx + dy * dy).sqrt();
+        let radius = thickness / 2.0;
+        if length < 0.001 {
+            self.fill_circle(x0, y0, radius as i32, color);
+            return;
+        }
+
+        // Quad around the line, with round caps
+        let (nx, ny) = (-dy / length * radius, dx / length * radius);
+        let corners = [
+            ((x0 as f32 + nx).round() as i32, (y0 as f32 + ny).round() as i32),
+            ((x1 as f32 + nx).round() as i32, (y1 as f32 + ny).round() as i32),
+            ((x1 as f32 - nx).round() as i32, (y1 as f32 - ny).round() as i32),
+            ((x0 as f32 - nx).round() as i32, (y0 as f32 - ny).round() as i32),
+        ];
+        self.fill_polygon(&corners, color);
+        self.fill_circle(x0, y0, radius as i32, color);
+        self.fill_circle(x1, y1, radius as i32, color);
+    }
+
+    pub fn draw_circle(&mut self, cx: i32, cy: i32, radius: i32, color: [u8; 4]) {
+        self.draw_ellipse(cx, cy, radius, radius, color);
+    }
+
+    pub fn fill_circle(&mut self, cx: i32, cy: i32, radius: i32, color: [u8; 4]) {
+        self.fill_ellipse(cx, cy, radius, radius, color);
+    }
+
+    pub fn draw_ellipse(&mut self, cx: i32, cy: i32, rx: i32, ry: i32, color: [u8; 4]) {
+        self.walk_ellipse(cx, cy, rx, ry, |canvas, x, y| {
+            let (left, right) = (cx.saturating_sub(x), cx.saturating_add(x));
+            let (top, bottom) = (cy.saturating_sub(y), cy.saturating_add(y));
+            canvas.plot(right, bottom, color);
+            canvas.plot(left, bottom, color);
+            canvas.plot(right, top, color);
+            canvas.plot(left, top, color);
+        });
+    }
+
+    pub fn fill_ellipse(&mut self, cx: i32, cy: i32, rx: i32, ry: i32, color: [u8; 4]) {
+        self.walk_ellipse(cx, cy, rx, ry, |canvas, x, y| {
+            let (left, right) = (cx.saturating_sub(x), cx.saturating_add(x));
+            canvas.draw_span(left, right, cy.saturating_add(y), color);
+            canvas.draw_span(left, right, cy.saturating_sub(y), color);
+        });
+    }
+
+    // Midpoint ellipse algorithm; calls visit for every point in the first
+    // quadrant, which the callers mirror into the other three. The terms
+    // grow with the cube of the radius, hence i128.
+    fn walk_ellipse(&mut self, cx: i32, cy: i32, rx: i32, ry: i32, mut visit: impl FnMut(&mut Self, i32, i32)) {
+        if rx < 0 || ry < 0 {
+            return;
+        }
+        let (cx, cy, rx64, ry64) = (cx as i64, cy as i64, rx as i64, ry as i64);
+        let (width, height) = (self.width as i64, self.height as i64);
+        if cx + rx64 < 0 || cy + ry64 < 0 || cx - rx64 >= width || cy - ry64 >= height {
+            return;
+        }
+        if rx == 0 || ry == 0 {
+            for x in visible_offsets(cx, width, rx64) {
+                for y in visible_offsets(cy, height, ry64) {
+                    visit(self, x as i32, y as i32);
+                }
+            }
+            return;
+        }
+
+        // The walk takes a step per pixel of radius, so ellipses larger than
+        // the canvas only visit the rows and columns that land on it
+        if rx64 > width + height || ry64 > width + height {
+            let (rx, ry) = (rx as f64, ry as f64);
+            for y in visible_offsets(cy, height, ry64) {
+                let x = rx * (1.0 - (y as f64 / ry).powi(2)).max(0.0).sqrt();
+                visit(self, x.round() as i32, y as i32);
+            }
+            for x in visible_offsets(cx, width, rx64) {
+                let y = ry * (1.0 - (x as f64 / rx).powi(2)).max(0.0).sqrt();
+                visit(self, x as i32, y.round() as i32);
+            }
+            return;
+        }
+
+        let (rx2, ry2) = (rx as i128 * rx as i128, ry as i128 * ry as i128);
+        let (mut x, mut y) = (0i128, ry as i128);
+        let mut px = 0i128;
+        let mut py = 2 * rx2 * y;
+
+        // Region 1: slope shallower than -1
+        let mut p = ry2 - rx2 * ry as i128 + rx2 / 4;
+        while px < py {
+            visit(self, x as i32, y as i32);
+            x += 1;
+            px += 2 * ry2;
+            if p < 0 {
+                p += ry2 + px;
+            } else {
+                y -= 1;
+                py -= 2 * rx2;
+                p += ry2 + px - py;
+            }
+        }
+
+        // Region 2: slope steeper than -1
+        let mut p = (ry2 as f64 * (x as f64 + 0.5).powi(2)
+            + rx2 as f64 * ((y - 1) as f64).powi(2)
+            - (rx2 * ry2) as f64) as i128;
+        while y >= 0 {
+            visit(self, x as i32, y as i32);
+            y -= 1;
+            py -= 2 * rx2;
+            if p > 0 {
+                p += rx2 - py;
+            } else {
+                x += 1;
+                px += 2 * ry2;
+                p += rx2 - py + px;
+            }
+        }
+    }
+
+    pub fn fill_triangle(&mut self, a: (i32, i32), b: (i32, i32), c: (i32, i32), color: [u8; 4]) {
+        self.fill_polygon(&[a, b, c], color);
+    }
+
+    pub fn draw_polygon(&mut self, points: &[(i32, i32)], color: [u8; 4]) {
+        for (i, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(i + 1) % points.len()];
+            self.draw_line(x0, y0, x1, y1, color);
+        }
+    }
+
+    // Scanline fill with the even-odd rule, sampling at pixel centers.
+    // Works for concave and self-intersecting polygons.
+    pub fn fill_polygon(&mut self, points: &[(i32, i32)], color: [u8; 4]) {
+        if points.len() < 3 {
+            return;
+        }
+
+        let min_y = points.iter().map(|p| p.1).min().unwrap_or(0).max(0);
+        let max_y = points.iter().map(|p| p.1).max().unwrap_or(0).min(self.height as i32 - 1);
+        let mut crossings = Vec::with_capacity(points.len());
+
+        for y in min_y..=max_y {
+            let sample_y = y as f32 + 0.5;
+            crossings.clear();
+
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                let (y0f, y1f) = (y0 as f32, y1 as f32);
+                if (y0f <= sample_y && y1f > sample_y) || (y1f <= sample_y && y0f > sample_y) {
+                    let t = (sample_y - y0f) / (y1f - y0f);
+                    crossings.push(x0 as f32 + t * (x1 as i64 - x0 as i64) as f32);
+                }
+            }
+
+            crossings.sort_by(|a, b| a.total_cmp(b));
+            for pair in crossings.chunks_exact(2) {
+                let start = (pair[0] - 0.5).ceil();
+                let end = (pair[1] - 0.5).ceil() - 1.0;
+                if start <= end {
+                    let clamp = |value: f32| value.clamp(-1.0, self.width as f32) as i32;
+                    self.draw_span(clamp(start), clamp(end), y, color);
+                }
+            }
+        }
+    }
+
+    pub fn blit(&mut self, image: &PixelImage, x: i32, y: i32, options: &BlitOptions) {
+        let source = options.source.unwrap_or(PixelRect {
+            x: 0,
+            y: 0,
+            width: image.width,
+            height: image.height,
+        });
+
+        // Clip the source rectangle to the image
+        let src_x = source.x.min(image.width);
+        let src_y = source.y.min(image.height);
+        let src_width = source.width.min(image.width - src_x);
+        let src_height = source.height.min(image.height - src_y);
+        if src_width == 0 || src_height == 0 || !(options.scale.is_finite() && options.scale > 0.0) {
+            return;
+        }
+
+        let dst_width = (src_width as f32 * options.scale).round() as i64;
+        let dst_height = (src_height as f32 * options.scale).round() as i64;
+
+        // Only walk destination pixels that land on the canvas
+        let start_x = (x as i64).max(0);
+        let start_y = (y as i64).max(0);
+        let end_x = (x as i64).saturating_add(dst_width).min(self.width as i64);
+        let end_y = (y as i64).saturating_add(dst_height).min(self.height as i64);
+
+        for py in start_y..end_y {
+            let mut v = ((py - y as i64) as f32 / options.scale) as u32;
+            v = v.min(src_height - 1);
+            if options.flip_y {
+                v = src_height - 1 - v;
+            }
+
+            for px in start_x..end_x {
+                let mut u = ((px - x as i64) as f32 / options.scale) as u32;
+                u = u.min(src_width - 1);
+                if options.flip_x {
+                    u = src_width - 1 - u;
+                }
+
+                let color = image.get_pixel(src_x + u, src_y + v);
+                if options.color_key == Some(color) {
+                    continue;
+                }
+                self.set_pixel(px as u32, py as u32, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn canvas() -> PixelCanvas {
+        PixelCanvas::new(16, 12)
+    }
+
+    fn count(canvas: &PixelCanvas, color: [u8; 4]) -> usize {
+        canvas.pixels.chunks_exact(4).filter(|pixel| **pixel == color).count()
+    }
+
+    #[test]
+    fn image_sizes_from_headers_are_checked() {
+        assert!(PixelImage::from_rgba(u32::MAX, u32::MAX, Vec::new()).is_none());
+        assert!(PixelImage::from_rgba(2, 1, vec![0; 8]).is_some());
+
+        let path = std::env::temp_dir().join(format!("raycaster_test_{}.ppm", std::process::id()));
+        std::fs::write(&path, b"P6 40000 40000 255\n\x01\x02\x03").unwrap();
+        assert!(load_ppm(&path).is_none());
+        std::fs::write(&path, b"P6 1 1 255\n\x01\x02\x03").unwrap();
+        assert_eq!(load_ppm(&path).map(|image| image.get_pixel(0, 0)), Some([1, 2, 3, 255]));
+        let _ = std::fs::remove_file(&path);
+
+        assert!(is_asset_name("default"));
+        assert!(!is_asset_name("../default") && !is_asset_name("a/b") && !is_asset_name("a\\b") && !is_asset_name(""));
+    }
+
+    #[test]
+    fn shapes_on_canvas() {
+        let mut canvas = canvas();
+        canvas.fill_circle(8, 6, 3, RED);
+        assert_eq!(canvas.get_pixel(8, 6), RED);
+        assert_eq!(canvas.get_pixel(8, 3), RED);
+        assert_eq!(canvas.get_pixel(0, 0), CLEAR);
+
+        let mut canvas = self::canvas();
+        canvas.draw_line(0, 0, 15, 11, RED);
+        assert_eq!(canvas.get_pixel(0, 0), RED);
+        assert_eq!(canvas.get_pixel(15, 11), RED);
+
+        let mut canvas = self::canvas();
+        canvas.fill_triangle((0, 0), (16, 0), (0, 12), RED);
+        assert_eq!(canvas.get_pixel(1, 1), RED);
+        assert_eq!(canvas.get_pixel(15, 11), CLEAR);
+    }
+
+    #[test]
+    fn shapes_off_canvas_draw_nothing() {
+        let mut canvas = canvas();
+        canvas.fill_circle(i32::MIN, i32::MIN, 1000, RED);
+        canvas.draw_circle(i32::MAX, 5, 10, RED);
+        canvas.fill_ellipse(-100, 6, 50, 50, RED);
+        canvas.draw_line(-100, -100, -10, -50, RED);
+        canvas.draw_line(i32::MIN, 20, i32::MAX, 20, RED);
+        canvas.fill_rect(i32::MIN, i32::MIN, i32::MAX, i32::MAX, RED);
+        canvas.fill_polygon(&[(-50, -50), (-10, -50), (-30, -10)], RED);
+        canvas.blit(&PixelImage::new(4, 4), i32::MIN, i32::MAX, &BlitOptions::default());
+        assert_eq!(count(&canvas, RED), 0);
+    }
+
+    #[test]
+    fn extreme_shapes_clip_to_canvas() {
+        let mut canvas = canvas();
+        canvas.fill_circle(0, 0, 70000, RED);
+        assert_eq!(count(&canvas, RED), 16 * 12);
+
+        let mut canvas = self::canvas();
+        canvas.draw_ellipse(8, 6, 100000, 3, RED);
+        assert!(count(&canvas, RED) > 0);
+
+        let mut canvas = self::canvas();
+        canvas.draw_line(i32::MIN, i32::MIN, i32::MAX, i32::MAX, RED);
+        assert_eq!(canvas.get_pixel(5, 5), RED);
+
+        let mut canvas = self::canvas();
+        canvas.fill_polygon(&[(i32::MIN, -10), (i32::MAX, -10), (0, i32::MAX)], RED);
+        assert_eq!(canvas.get_pixel(8, 6), RED);
+
+        let mut canvas = self::canvas();
+        canvas.draw_thick_line(i32::MIN, 1, i32::MAX, 1, 4.0, RED);
+        assert_eq!(canvas.get_pixel(5, 1), RED);
+
+        let mut canvas = self::canvas();
+        canvas.fill_rect(-5, -5, i32::MAX, i32::MAX, RED);
+        assert_eq!(count(&canvas, RED), 16 * 12);
+
+        // Centered on the canvas, but the outline lies far outside it
+        let mut canvas = self::canvas();
+        canvas.draw_circle(8, 6, i32::MAX, RED);
+        assert_eq!(count(&canvas, RED), 0);
+        canvas.fill_circle(8, 6, i32::MAX, RED);
+        assert_eq!(count(&canvas, RED), 16 * 12);
+
+        let mut canvas = self::canvas();
+        canvas.fill_ellipse(8, 6, i32::MAX, 0, RED);
+        assert_eq!(count(&canvas, RED), 16);
+        canvas.draw_ellipse(3, 6, 0, i32::MAX, RED);
+        assert_eq!(count(&canvas, RED), 16 + 11);
+    }
+
+    #[test]
+    fn blit_rejects_bad_scales() {
+        let image = PixelImage::from_rgba(2, 2, vec![255; 16]).unwrap();
+        for scale in [f32::INFINITY, f32::NAN, f32::MAX, 0.0, -1.0] {
+            let mut canvas = canvas();
+            canvas.blit(&image, i32::MAX - 1, 0, &BlitOptions { scale, ..BlitOptions::default() });
+            canvas.blit(&image, 0, 0, &BlitOptions { scale, ..BlitOptions::default() });
+            let expected = if scale == f32::MAX { 16 * 12 } else { 0 };
+            assert_eq!(count(&canvas, [255; 4]), expected, "{}", scale);
+        }
+    }
+}
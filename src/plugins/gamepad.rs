@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use super::player::{PlayerInput, PlayerSystems};
+
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(GamepadSettings::default())
+            .add_systems(PreUpdate, gather_gamepad_input.in_set(PlayerSystems::GatherInput));
+    }
+}
+
+#[derive(Resource)]
+pub struct GamepadSettings {
+    pub move_deadzone: f32,
+    pub look_deadzone: f32,
+    pub response_exponent: f32, // 1.0 = linear, 2.0 = quadratic (finer control near center)
+    pub yaw_speed: f32,         // Radians per second at full deflection
+    pub pitch_speed: f32,       // Radians per second at full deflection
+    pub invert_y: bool,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            move_deadzone: 0.15,
+            look_deadzone: 0.12,
+            response_exponent: 2.0,
+            yaw_speed: 3.0,
+            pitch_speed: 1.0,
+            invert_y: false,
+        }
+    }
+}
+
+// Radial deadzone followed by a power curve, so output magnitude is 0..=1
+// and the stick direction is preserved
+pub fn apply_stick_response(stick: Vec2, deadzone: f32, exponent: f32) -> Vec2 {
+    let magnitude = stick.length();
+    if magnitude <= deadzone {
+        return Vec2::ZERO;
+    }
+
+    let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0);
+    stick / magnitude * scaled.powf(exponent)
+}
+
+fn gather_gamepad_input(
+    gamepads: Query<&Gamepad>,
+    settings: Res<GamepadSettings>,
+    mut input: ResMut<PlayerInput>,
+) {
+    for gamepad in gamepads.iter() {
+        // Stick magnitude scales move_speed instead of acting as on/off
+        let movement = apply_stick_response(
+            gamepad.left_stick(),
+            settings.move_deadzone,
+            settings.response_exponent,
+        );
+        input.forward += movement.y;
+        input.strafe += movement.x;
+        
+        let look = apply_stick_response(
+            gamepad.right_stick(),
+            settings.look_deadzone,
+            settings.response_exponent,
+        );
+        let pitch_sign = if settings.invert_y { -1.0 } else { 1.0 };
+        input.look_yaw += look.x * settings.yaw_speed * input.dt;
+        input.look_pitch += look.y * pitch_sign * settings.pitch_speed * input.dt;
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use std::path::Path;
+use super::actions::{Action, ActionState, InputMap};
+use super::canvas::{PixelCanvas, CanvasPass};
+use super::player::Player;
+use super::text::{BitmapFont, TextStyle};
+
+// The first of these that exists replaces the bundled font
+pub const HUD_FONT_PATHS: &[&str] = &["assets/fonts/hud.psf", "assets/fonts/hud.fnt"];
+const MESSAGE_DURATION: f32 = 4.0;
+const MAX_MESSAGES: usize = 5;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(HudFont(load_hud_font()))
+            .insert_resource(HudState::default())
+            .insert_resource(HudMessages::default())
+            .add_systems(Update, (
+                handle_hud_controls,
+                expire_messages,
+                draw_hud.in_set(CanvasPass::Overlay),
+            ));
+    }
+}
+
+#[derive(Resource)]
+pub struct HudFont(pub BitmapFont);
+
+#[derive(Resource)]
+pub struct HudState {
+    pub show_fps: bool,
+    pub show_position: bool,
+    pub show_help: bool,
+}
+
+impl Default for HudState {
+    fn default() -> Self {
+        Self {
+            show_fps: true,
+            show_position: false,
+            show_help: false,
+        }
+    }
+}
+
+struct HudMessage {
+    text: String,
+    remaining: f32,
+}
+
+// Short-lived messages shown in the bottom-left corner; any system can push
+#[derive(Resource, Default)]
+pub struct HudMessages {
+    messages: Vec<HudMessage>,
+}
+
+impl HudMessages {
+    pub fn push(&mut self, text: impl Into<String>) {
+        self.messages.push(HudMessage {
+            text: text.into(),
+            remaining: MESSAGE_DURATION,
+        });
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+}
+
+fn load_hud_font() -> BitmapFont {
+    for path in HUD_FONT_PATHS.iter().map(Path::new).filter(|path| path.exists()) {
+        match BitmapFont::load(path) {
+            Ok(font) => {
+                info!("HUD font loaded from {}", path.display());
+                return font;
+            }
+            Err(err) => warn!("Could not load font {}: {}", path.display(), err),
+        }
+    }
+    BitmapFont::builtin()
+}
+
+fn handle_hud_controls(
+    actions: Res<ActionState>,
+    mut state: ResMut<HudState>,
+) {
+    if actions.just_pressed(Action::ShowHelp) {
+        state.show_help = !state.show_help;
+    }
+
+    if actions.just_pressed(Action::ShowPlayerInfo) {
+        state.show_position = !state.show_position;
+    }
+}
+
+fn expire_messages(
+    mut messages: ResMut<HudMessages>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    messages.messages.retain_mut(|message| {
+        message.remaining -= dt;
+        message.remaining > 0.0
+    });
+}
+
+fn draw_hud(
+    mut canvas: ResMut<PixelCanvas>,
+    font: Res<HudFont>,
+    state: Res<HudState>,
+    messages: Res<HudMessages>,
+    diagnostics: Res<DiagnosticsStore>,
+    player: Res<Player>,
+    input_map: Res<InputMap>,
+) {
+    let font = &font.0;
+    let style = TextStyle::default();
+    let mut y = 2;
+
+    if state.show_fps {
+        let fps = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.smoothed())
+            .unwrap_or(0.0);
+        y += canvas.draw_text(font, &format!("{{yellow}}FPS{{/}} {:.0}", fps), 2, y, &style) as i32;
+    }
+
+    if state.show_position {
+        let text = format!(
+            "{{cyan}}Pos{{/}} {:.2}, {:.2}  {{cyan}}Angle{{/}} {:.0} deg",
+            player.position.x, player.position.y, player.angle.to_degrees(),
+        );
+        canvas.draw_text(font, &text, 2, y, &style);
+    }
+
+    let mut message_y = canvas.height as i32 - 2;
+    for message in messages.messages.iter().rev() {
+        let (_, height) = font.measure(&message.text, &style);
+        message_y -= height as i32;
+        canvas.draw_text(font, &message.text, 2, message_y, &style);
+    }
+
+    if state.show_help {
+        draw_help_panel(&mut canvas, font, &input_map);
+    }
+}
+
+fn draw_help_panel(canvas: &mut PixelCanvas, font: &BitmapFont, input_map: &InputMap) {
+    let mut text = String::from("{yellow}Controls{/}");
+    for (action, bindings) in &input_map.bindings {
+        let names: Vec<String> = bindings.iter().map(|binding| binding.to_config_string()).collect();
+        text.push_str(&format!("\n{{gray}}{}{{/}} {}", action.name(), names.join(", ")));
+    }
+
+    let style = TextStyle {
+        max_width: Some(canvas.width - 40),
+        ..default()
+    };
+    let (width, height) = font.measure(&text, &style);
+    let x = (canvas.width as i32 - width as i32) / 2;
+    let y = (canvas.height as i32 - height as i32) / 2;
+
+    canvas.fill_rect(x - 6, y - 6, width as i32 + 12, height as i32 + 12, [0, 0, 0, 170]);
+    canvas.draw_text(font, &text, x, y, &style);
+}
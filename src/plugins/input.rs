@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use super::canvas::PixelCanvas;
 use super::player::Player;
+use super::actions::{Action, ActionState, InputMap};
 
 pub struct InputPlugin;
 
@@ -14,36 +15,36 @@ impl Plugin for InputPlugin {
 }
 
 fn handle_canvas_controls(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut canvas: ResMut<PixelCanvas>,
     player: Option<Res<Player>>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
+    if actions.just_pressed(Action::RandomPixels) {
         info!("Drawing random pixels");
         draw_random_pixels(&mut canvas);
     }
     
-    if keyboard_input.just_pressed(KeyCode::KeyC) {
+    if actions.just_pressed(Action::ClearCanvas) {
         info!("Clearing canvas");
         canvas.clear([0, 0, 40, 255]);
     }
     
-    if keyboard_input.just_pressed(KeyCode::KeyR) {
+    if actions.just_pressed(Action::RedPattern) {
         info!("Drawing red pattern");
         draw_red_pattern(&mut canvas);
     }
     
-    if keyboard_input.just_pressed(KeyCode::KeyG) {
+    if actions.just_pressed(Action::GreenPattern) {
         info!("Drawing green pattern");
         draw_green_pattern(&mut canvas);
     }
     
-    if keyboard_input.just_pressed(KeyCode::KeyB) {
+    if actions.just_pressed(Action::BluePattern) {
         info!("Drawing blue pattern");
         draw_blue_pattern(&mut canvas);
     }
     
-    if keyboard_input.just_pressed(KeyCode::KeyP) {
+    if actions.just_pressed(Action::ShowPlayerInfo) {
         if let Some(player) = player {
             info!("Player pos: ({:.2}, {:.2}), angle: {:.2} rad ({:.1}°)", 
                   player.position.x, player.position.y, 
@@ -53,16 +54,29 @@ fn handle_canvas_controls(
 }
 
 fn handle_debug_controls(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
+    input_map: Res<InputMap>,
     mut exit: EventWriter<AppExit>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Escape) {
+    if actions.just_pressed(Action::Quit) {
         info!("Exit requested");
         exit.write(AppExit::Success);
     }
     
-    if keyboard_input.just_pressed(KeyCode::F1) {
-        info!("Controls: [WASD] Move, [Mouse] Look, [P] Player info, [M] Toggle minimap, [F1] Help, [Esc] Exit/Release mouse");
+    if actions.just_pressed(Action::ShowHelp) {
+        info!("Controls (edit {} to rebind):", super::actions::CONTROLS_CONFIG_PATH);
+        for (action, bindings) in &input_map.bindings {
+            let names: Vec<String> = bindings.iter().map(|binding| binding.to_config_string()).collect();
+            info!("   {} - [{}]", action.name(), names.join(", "));
+        }
+    }
+    
+    if actions.just_pressed(Action::Use) {
+        info!("Use");
+    }
+    
+    if actions.just_pressed(Action::Fire) {
+        info!("Fire");
     }
 }
 
@@ -0,0 +1,334 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use super::actions::{Action, ActionState};
+use super::canvas::CanvasPass;
+use super::debug::DebugOverlayAppExt;
+use super::map::GameMap;
+use super::math::Vec2f;
+use super::player::Player;
+use super::raycast::cast_ray;
+
+pub const DEFAULT_AMBIENT: f32 = 0.35;
+const MUZZLE_FLASH: MapLight = MapLight {
+    position: Vec2f { x: 0.0, y: 0.0 },
+    color: [255, 220, 150],
+    radius: 5.0,
+    intensity: 1.5,
+    flicker: 0.0,
+    enabled: true,
+};
+const MUZZLE_FLASH_SECONDS: f32 = 0.08;
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(LightMap::default())
+            .insert_resource(TransientLights::default())
+            .add_debug_page("Lighting", lighting_debug_page)
+            .add_systems(Update, (
+                (handle_muzzle_flash, expire_lights),
+                update_light_cache,
+                accumulate_lights,
+            ).chain().before(CanvasPass::World));
+    }
+}
+
+// A point light: torches and lamps placed in the map, muzzle flashes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapLight {
+    pub position: Vec2f,
+    pub color: [u8; 3],
+    pub radius: f32,
+    pub intensity: f32,
+    pub flicker: f32, // 0 = steady, 1 = can dip all the way to dark
+    pub enabled: bool,
+}
+
+impl MapLight {
+    pub fn new(position: Vec2f, color: [u8; 3], radius: f32) -> Self {
+        Self {
+            position,
+            color,
+            radius,
+            intensity: 1.0,
+            flicker: 0.0,
+            enabled: true,
+        }
+    }
+
+    // Only these affect which cells a light reaches; intensity, color and
+    // flicker are applied per frame from the cached falloff
+    fn same_reach(&self, other: &MapLight) -> bool {
+        self.position == other.position && self.radius == other.radius && self.enabled == other.enabled
+    }
+}
+
+// Short-lived lights such as muzzle flashes. They live outside GameMap so
+// that spawning and expiring them doesn't mark the whole map changed.
+#[derive(Resource, Default)]
+pub struct TransientLights {
+    lights: Vec<TransientLight>,
+    next_id: u32,
+}
+
+struct TransientLight {
+    id: u32,
+    light: MapLight,
+    remaining: f32, // Seconds
+}
+
+impl TransientLights {
+    pub fn spawn(&mut self, light: MapLight, seconds: f32) {
+        self.lights.push(TransientLight { id: self.next_id, light, remaining: seconds });
+        self.next_id = self.next_id.wrapping_add(1);
+    }
+}
+
+// Identifies a light across rebuilds, so removing one doesn't shift the
+// cache entries of the others. Map lights only change with the map itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LightId {
+    Map(usize),
+    Transient(u32),
+}
+
+struct CachedLight {
+    id: LightId,
+    light: MapLight,          // As it was when reach was traced
+    reach: Vec<(usize, f32)>, // Cells it lights and how strongly
+}
+
+// Light reaching each map cell. Each light's falloff, with walls already
+// taken into account, is cached; only lights that moved or whose area
+// had tiles change are traced again.
+#[derive(Resource)]
+pub struct LightMap {
+    pub ambient: f32,
+    width: usize,
+    height: usize,
+    cells: Vec<[f32; 3]>, // This frame's light, 1.0 = full brightness
+    cached: Vec<CachedLight>,
+    tiles: Vec<u8>,       // The map as it was when reach was traced
+    pub last_rebuilt: usize, // Lights traced again on the last change
+}
+
+impl Default for LightMap {
+    fn default() -> Self {
+        Self {
+            ambient: DEFAULT_AMBIENT,
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+            cached: Vec::new(),
+            tiles: Vec::new(),
+            last_rebuilt: 0,
+        }
+    }
+}
+
+impl LightMap {
+    pub fn is_active(&self) -> bool {
+        !self.cached.is_empty()
+    }
+
+    // Light at a point, blended between the four nearest cell centers
+    pub fn sample(&self, point: Vec2f) -> [f32; 3] {
+        if self.cells.is_empty() {
+            return [1.0; 3];
+        }
+
+        let x = (point.x - 0.5).clamp(0.0, self.width as f32 - 1.0);
+        let y = (point.y - 0.5).clamp(0.0, self.height as f32 - 1.0);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+        let cell = |x: usize, y: usize| self.cells[y * self.width + x];
+        std::array::from_fn(|channel| {
+            let top = cell(x0, y0)[channel] * (1.0 - tx) + cell(x1, y0)[channel] * tx;
+            let bottom = cell(x0, y1)[channel] * (1.0 - tx) + cell(x1, y1)[channel] * tx;
+            self.ambient + top * (1.0 - ty) + bottom * ty
+        })
+    }
+
+    // Overall light at a point, for when color doesn't matter
+    pub fn brightness(&self, point: Vec2f) -> f32 {
+        let light = self.sample(point);
+        (light[0] + light[1] + light[2]) / 3.0
+    }
+
+    pub fn shade(&self, color: [u8; 4], point: Vec2f) -> [u8; 4] {
+        let light = self.sample(point);
+        let channel = |index: usize| (color[index] as f32 * light[index]).min(255.0) as u8;
+        [channel(0), channel(1), channel(2), color[3]]
+    }
+
+    fn rebuild(&mut self, map: &GameMap, transient: &TransientLights, map_changed: bool) {
+        let resized = self.width != map.width || self.height != map.height;
+        if resized {
+            self.width = map.width;
+            self.height = map.height;
+            self.cells = vec![[0.0; 3]; map.width * map.height];
+            self.cached.clear();
+            self.tiles.clone_from(&map.tiles);
+        }
+
+        // Cells whose tile changed since the last trace, e.g. a door opening
+        let mut changed = Vec::new();
+        if map_changed && self.tiles != map.tiles {
+            changed = self.tiles.iter()
+                .zip(&map.tiles)
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(index, _)| Vec2f::new((index % map.width) as f32 + 0.5, (index / map.width) as f32 + 0.5))
+                .collect();
+            self.tiles.clone_from(&map.tiles);
+        }
+
+        let lights = map.lights.iter().enumerate()
+            .map(|(index, light)| (LightId::Map(index), *light))
+            .chain(transient.lights.iter().map(|flash| (LightId::Transient(flash.id), flash.light)));
+        let mut previous: HashMap<LightId, CachedLight> = self.cached.drain(..).map(|entry| (entry.id, entry)).collect();
+        self.last_rebuilt = 0;
+        for (id, light) in lights {
+            let reused = previous.remove(&id).filter(|old| {
+                old.light.same_reach(&light)
+                    && !changed.iter().any(|cell| (*cell - light.position).length() <= light.radius + 1.0)
+            });
+            let reach = match reused {
+                Some(old) => old.reach,
+                None => {
+                    self.last_rebuilt += 1;
+                    trace_light(&light, map)
+                }
+            };
+            self.cached.push(CachedLight { id, light, reach });
+        }
+    }
+}
+
+// Open cells within the radius that the light can see, with falloff
+fn trace_light(light: &MapLight, map: &GameMap) -> Vec<(usize, f32)> {
+    if !light.enabled || light.radius <= 0.0 {
+        return Vec::new();
+    }
+
+    let min_x = (light.position.x - light.radius).floor().max(0.0) as usize;
+    let min_y = (light.position.y - light.radius).floor().max(0.0) as usize;
+    let max_x = ((light.position.x + light.radius).ceil() as usize).min(map.width);
+    let max_y = ((light.position.y + light.radius).ceil() as usize).min(map.height);
+
+    let mut reach = Vec::new();
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            if map.get_tile(x, y) != 0 {
+                continue;
+            }
+
+            let center = Vec2f::new(x as f32 + 0.5, y as f32 + 0.5);
+            let offset = center - light.position;
+            let distance = offset.length();
+            if distance >= light.radius {
+                continue;
+            }
+
+            // With the offset as the direction, a hit before t = 1 lies between them
+            let occluded = distance > 0.001 && cast_ray(&light.position, offset, map, 1.0).is_some();
+            if !occluded {
+                let falloff = 1.0 - distance / light.radius;
+                reach.push((y * map.width + x, falloff * falloff));
+            }
+        }
+    }
+    reach
+}
+
+// Smooth pseudo-random wobble in [0, 1], different for every light
+fn flicker_noise(time: f32, seed: usize) -> f32 {
+    let hash = |n: i32| {
+        let n = (n.wrapping_mul(374761393) ^ (seed as i32).wrapping_mul(668265263)) as u32;
+        let n = (n ^ (n >> 13)).wrapping_mul(1274126177);
+        (n ^ (n >> 16)) as f32 / u32::MAX as f32
+    };
+    let t = time * 12.0;
+    let i = t.floor();
+    let f = t - i;
+    let smooth = f * f * (3.0 - 2.0 * f);
+    hash(i as i32) * (1.0 - smooth) + hash(i as i32 + 1) * smooth
+}
+
+fn update_light_cache(
+    map: Option<Res<GameMap>>,
+    transient: Res<TransientLights>,
+    mut light_map: ResMut<LightMap>,
+) {
+    let Some(map) = map else { return };
+    if map.is_changed() || transient.is_changed() {
+        light_map.rebuild(&map, &transient, map.is_changed());
+    }
+}
+
+fn accumulate_lights(
+    mut light_map: ResMut<LightMap>,
+    time: Res<Time>,
+) {
+    let light_map = &mut *light_map;
+    if !light_map.is_active() {
+        return;
+    }
+
+    light_map.cells.fill([0.0; 3]);
+    let now = time.elapsed_secs();
+    for (index, entry) in light_map.cached.iter().enumerate() {
+        let light = &entry.light;
+        let strength = light.intensity * (1.0 - light.flicker * flicker_noise(now, index));
+        let color = light.color.map(|channel| channel as f32 / 255.0 * strength);
+        for &(cell, falloff) in &entry.reach {
+            for channel in 0..3 {
+                light_map.cells[cell][channel] += color[channel] * falloff;
+            }
+        }
+    }
+}
+
+fn handle_muzzle_flash(
+    actions: Res<ActionState>,
+    player: Res<Player>,
+    mut transient: ResMut<TransientLights>,
+) {
+    if actions.just_pressed(Action::Fire) {
+        let light = MapLight { position: player.position + player.direction * 0.3, ..MUZZLE_FLASH };
+        transient.spawn(light, MUZZLE_FLASH_SECONDS);
+    }
+}
+
+fn expire_lights(
+    mut transient: ResMut<TransientLights>,
+    time: Res<Time>,
+) {
+    // Counting down alone leaves the cached light as it was, so only a
+    // light going out marks the resource changed
+    let dt = time.delta_secs();
+    let lights = &mut transient.bypass_change_detection().lights;
+    let before = lights.len();
+    lights.retain_mut(|flash| {
+        flash.remaining -= dt;
+        flash.remaining > 0.0
+    });
+    if lights.len() != before {
+        transient.set_changed();
+    }
+}
+
+fn lighting_debug_page(world: &World) -> Vec<String> {
+    let light_map = world.resource::<LightMap>();
+    let lit_cells: usize = light_map.cached.iter().map(|entry| entry.reach.len()).sum();
+    vec![
+        format!("Lights: {}", light_map.cached.len()),
+        format!("Lit cells: {}", lit_cells),
+        format!("Ambient: {:.2}", light_map.ambient),
+        format!("Traced on last change: {}", light_map.last_rebuilt),
+    ]
+}
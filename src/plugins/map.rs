@@ -1,5 +1,11 @@
 use bevy::prelude::*;
+use std::collections::BTreeMap;
 use super::math::Vec2f;
+use super::portal::{Face, PortalFace, PortalLink};
+use super::segment::{WallSegment, SEGMENT_CLEARANCE};
+use super::lighting::MapLight;
+use super::sky::DEFAULT_SKY;
+use super::surface::WallSurface;
 
 pub struct MapPlugin;
 
@@ -9,34 +15,277 @@ impl Plugin for MapPlugin {
     }
 }
 
-#[derive(Resource)]
+// What the map looks like past its edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutOfBounds {
+    #[default]
+    Solid, // Everything outside is BORDER_TILE wall
+    Wrap,  // The map repeats in both directions
+    Void,  // Everything outside is empty; rays fly until the max distance
+}
+
+impl OutOfBounds {
+    pub fn name(self) -> &'static str {
+        match self {
+            OutOfBounds::Solid => "solid",
+            OutOfBounds::Wrap => "wrap",
+            OutOfBounds::Void => "void",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OutOfBounds> {
+        [OutOfBounds::Solid, OutOfBounds::Wrap, OutOfBounds::Void]
+            .into_iter()
+            .find(|policy| policy.name() == name)
+    }
+}
+
+pub const BORDER_TILE: u8 = 1;
+pub const MIRROR_TILE: u8 = 6;
+pub const SCREEN_TILE: u8 = 7;
+pub const CONVEYOR_TILE: u8 = 8;
+pub const WATERFALL_TILE: u8 = 9;
+pub const SWITCH_OFF_TILE: u8 = 10;
+pub const SWITCH_ON_TILE: u8 = 11;
+pub const ICE_FLOOR: u8 = 1;
+pub const MUD_FLOOR: u8 = 2;
+
+// A reflective wall type; rays bounce off it and pick up its color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mirror {
+    pub color: [u8; 3],
+    pub reflectivity: f32, // 0 = plain wall, 1 = perfect mirror
+}
+
+// How a floor type looks and feels underfoot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloorMaterial {
+    pub color: [u8; 3],
+    pub friction: f32, // Grip relative to plain ground; lower slides further
+    pub speed: f32,    // Top speed relative to plain ground
+}
+
+impl FloorMaterial {
+    pub const GROUND: FloorMaterial = FloorMaterial {
+        color: [34, 139, 34], // Forest green
+        friction: 1.0,
+        speed: 1.0,
+    };
+}
+
+#[derive(Resource, Clone)]
 pub struct GameMap {
     pub width: usize,
     pub height: usize,
-    pub tiles: Vec<Vec<u8>>,
+    pub tiles: Vec<u8>, // Row-major, width * height
+    pub out_of_bounds: OutOfBounds,
+    pub portals: Vec<PortalLink>,
+    pub mirrors: BTreeMap<u8, Mirror>, // Wall type -> mirror
+    pub segments: BTreeMap<(i32, i32), Vec<WallSegment>>, // Thin walls inside open cells
+    pub lights: Vec<MapLight>,
+    pub sky: String, // Name of the sky panorama under assets/sky
+    pub ceilings: Vec<u8>, // Per cell like tiles, 0 = open to the sky; empty when the map has none
+    pub surfaces: BTreeMap<u8, WallSurface>, // Wall type -> textured look
+    pub switches: BTreeMap<u8, u8>, // Wall type -> what it turns into when used
+    pub floors: Vec<u8>, // Per cell like tiles, 0 = plain ground; empty when the map has none
+    pub floor_materials: BTreeMap<u8, FloorMaterial>, // Floor type -> material
 }
 
 impl GameMap {
     pub fn new(width: usize, height: usize) -> Self {
-        let tiles = vec![vec![0; width]; height];
-        Self { width, height, tiles }
+        Self {
+            width,
+            height,
+            tiles: vec![0; width * height],
+            out_of_bounds: OutOfBounds::default(),
+            portals: Vec::new(),
+            mirrors: BTreeMap::new(),
+            segments: BTreeMap::new(),
+            lights: Vec::new(),
+            sky: DEFAULT_SKY.to_string(),
+            ceilings: Vec::new(),
+            surfaces: BTreeMap::new(),
+            switches: BTreeMap::new(),
+            floors: Vec::new(),
+            floor_materials: BTreeMap::new(),
+        }
+    }
+    
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
     }
     
     pub fn get_tile(&self, x: usize, y: usize) -> u8 {
+        if x < self.width && y < self.height {
+            return self.tiles[y * self.width + x];
+        }
+        self.tile_at(x.min(i32::MAX as usize) as i32, y.min(i32::MAX as usize) as i32)
+    }
+    
+    // Any cell, inside or outside the map, following the out-of-bounds policy
+    pub fn tile_at(&self, x: i32, y: i32) -> u8 {
+        if self.in_bounds(x, y) {
+            return self.tiles[y as usize * self.width + x as usize];
+        }
+        
+        match self.out_of_bounds {
+            OutOfBounds::Solid => BORDER_TILE,
+            OutOfBounds::Void => 0,
+            OutOfBounds::Wrap => {
+                let (x, y) = self.wrap_cell(x, y);
+                self.tiles[y as usize * self.width + x as usize]
+            }
+        }
+    }
+    
+    pub fn set_tile(&mut self, x: usize, y: usize, tile: u8) {
+        if x < self.width && y < self.height {
+            self.tiles[y * self.width + x] = tile;
+        }
+    }
+    
+    // Outside the map is always open sky
+    pub fn ceiling_at(&self, x: i32, y: i32) -> u8 {
+        if self.ceilings.is_empty() {
+            return 0;
+        }
+        let (x, y) = self.wrap_cell(x, y);
+        if !self.in_bounds(x, y) {
+            return 0;
+        }
+        self.ceilings[y as usize * self.width + x as usize]
+    }
+    
+    pub fn set_ceiling(&mut self, x: usize, y: usize, ceiling: u8) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        if self.ceilings.is_empty() {
+            self.ceilings = vec![0; self.width * self.height];
+        }
+        self.ceilings[y * self.width + x] = ceiling;
+    }
+    
+    // Outside the map is always plain ground
+    pub fn floor_at(&self, x: i32, y: i32) -> u8 {
+        if self.floors.is_empty() {
+            return 0;
+        }
+        let (x, y) = self.wrap_cell(x, y);
+        if !self.in_bounds(x, y) {
+            return 0;
+        }
+        self.floors[y as usize * self.width + x as usize]
+    }
+    
+    pub fn set_floor(&mut self, x: usize, y: usize, floor: u8) {
         if x >= self.width || y >= self.height {
-            return 1; // Wall if out of bounds
+            return;
+        }
+        if self.floors.is_empty() {
+            self.floors = vec![0; self.width * self.height];
         }
-        self.tiles[y][x]
+        self.floors[y * self.width + x] = floor;
+    }
+    
+    // Floor types without a material are plain ground
+    pub fn floor_material(&self, pos: Vec2f) -> FloorMaterial {
+        let floor = self.floor_at(pos.x.floor() as i32, pos.y.floor() as i32);
+        self.floor_materials.get(&floor).copied().unwrap_or(FloorMaterial::GROUND)
+    }
+    
+    pub fn row(&self, y: usize) -> &[u8] {
+        &self.tiles[y * self.width..(y + 1) * self.width]
+    }
+    
+    // The in-map cell a coordinate refers to when the map wraps; other
+    // policies leave it unchanged
+    pub fn wrap_cell(&self, x: i32, y: i32) -> (i32, i32) {
+        match self.out_of_bounds {
+            OutOfBounds::Wrap => (x.rem_euclid(self.width as i32), y.rem_euclid(self.height as i32)),
+            _ => (x, y),
+        }
+    }
+    
+    // Portal faces belong to wall cells; rays and the player reaching one
+    // carry on out of the other
+    pub fn link_portals(&mut self, a: PortalFace, b: PortalFace) {
+        self.portals.retain(|link| ![a, b].iter().any(|face| link.a == *face || link.b == *face));
+        self.portals.push(PortalLink { a, b });
+    }
+    
+    pub fn portal_exit(&self, entry: PortalFace) -> Option<PortalFace> {
+        self.portals.iter().find_map(|link| {
+            if link.a == entry {
+                Some(link.b)
+            } else if link.b == entry {
+                Some(link.a)
+            } else {
+                None
+            }
+        })
+    }
+    
+    pub fn mirror(&self, wall_type: u8) -> Option<&Mirror> {
+        self.mirrors.get(&wall_type)
+    }
+    
+    pub fn add_segment(&mut self, x: i32, y: i32, segment: WallSegment) {
+        self.segments.entry((x, y)).or_default().push(segment);
+    }
+    
+    pub fn segments_at(&self, x: i32, y: i32) -> &[WallSegment] {
+        if self.segments.is_empty() {
+            return &[];
+        }
+        self.segments.get(&self.wrap_cell(x, y)).map_or(&[], Vec::as_slice)
+    }
+    
+    // Thin walls block a move that passes through them or ends too close
+    pub fn segment_blocks(&self, from: Vec2f, to: Vec2f) -> bool {
+        if self.segments.is_empty() {
+            return false;
+        }
+        
+        let (cell_x, cell_y) = (to.x.floor() as i32, to.y.floor() as i32);
+        (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (cell_x + dx, cell_y + dy)))
+            .flat_map(|(x, y)| self.segments_at(x, y).iter().map(move |segment| segment.in_cell(x, y)))
+            .any(|segment| segment.crosses(from, to) || segment.distance_to(to) < SEGMENT_CLEARANCE)
+    }
+    
+    // Brings a position that walked off a wrapping map back onto it
+    pub fn wrap_position(&self, pos: Vec2f) -> Vec2f {
+        if self.out_of_bounds != OutOfBounds::Wrap {
+            return pos;
+        }
+        let wrap = |value: f32, size: usize| {
+            let wrapped = value.rem_euclid(size as f32);
+            if wrapped >= size as f32 { 0.0 } else { wrapped } // Rounding can land exactly on size
+        };
+        Vec2f::new(wrap(pos.x, self.width), wrap(pos.y, self.height))
+    }
+    
+    // Offset from one position to another; on a wrapping map this takes the
+    // short way across the seam
+    pub fn shortest_offset(&self, from: Vec2f, to: Vec2f) -> Vec2f {
+        let offset = to - from;
+        if self.out_of_bounds != OutOfBounds::Wrap {
+            return offset;
+        }
+        let shorten = |delta: f32, size: usize| {
+            let size = size as f32;
+            delta - size * (delta / size).round()
+        };
+        Vec2f::new(shorten(offset.x, self.width), shorten(offset.y, self.height))
     }
     
     pub fn is_wall(&self, x: f32, y: f32) -> bool {
-        let map_x = x as usize;
-        let map_y = y as usize;
-        self.get_tile(map_x, map_y) != 0
+        self.tile_at(x.floor() as i32, y.floor() as i32) != 0
     }
     
     pub fn is_valid_position(&self, pos: Vec2f) -> bool {
-        if pos.x < 0.0 || pos.y < 0.0 {
+        let wraps = self.out_of_bounds == OutOfBounds::Wrap;
+        if !wraps && !self.in_bounds(pos.x.floor() as i32, pos.y.floor() as i32) {
             return false;
         }
         !self.is_wall(pos.x, pos.y)
@@ -59,7 +308,7 @@ fn setup_map(mut commands: Commands) {
         [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],
         [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],
         [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],
-        [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],
+        [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,6,6,6,6,0,1],
         [1,0,0,0,0,0,0,0,0,0,0,4,4,4,4,4,0,0,0,0,0,0,0,1],
         [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,4,0,0,0,0,0,0,0,1],
         [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,4,0,0,0,0,0,0,0,1],
@@ -76,9 +325,67 @@ fn setup_map(mut commands: Commands) {
     
     for (y, row) in level_data.iter().enumerate() {
         for (x, &tile) in row.iter().enumerate() {
-            map.tiles[y][x] = tile;
+            map.set_tile(x, y, tile);
+        }
+    }
+    
+    // The west face of one blue pillar looks out of the east face of the other
+    map.link_portals(PortalFace::new(15, 4, Face::West), PortalFace::new(15, 6, Face::East));
+    map.mirrors.insert(MIRROR_TILE, Mirror { color: [170, 200, 220], reflectivity: 0.8 });
+    
+    // Thin walls: a cut-off corner, a fence and a post standing in the open
+    map.add_segment(1, 1, WallSegment::new(Vec2f::new(0.0, 1.0), Vec2f::new(1.0, 0.0), 1));
+    for x in 2..6 {
+        map.add_segment(x, 13, WallSegment::new(Vec2f::new(0.0, 0.0), Vec2f::new(1.0, 0.0), 3));
+    }
+    for segment in WallSegment::pillar(Vec2f::new(0.3, 0.7), 0.15, 5) {
+        map.add_segment(20, 20, segment);
+    }
+    
+    // A flickering torch in the first room, a cool lamp by the yellow maze
+    // and a dim green glow to the east
+    map.lights.push(MapLight { flicker: 0.35, ..MapLight::new(Vec2f::new(3.5, 3.5), [255, 170, 90], 7.0) });
+    map.lights.push(MapLight::new(Vec2f::new(10.5, 19.5), [180, 200, 255], 6.0));
+    map.lights.push(MapLight { intensity: 0.7, ..MapLight::new(Vec2f::new(20.5, 9.5), [120, 255, 140], 5.0) });
+    
+    // The yellow maze in the south-west is indoors
+    for y in 16..23 {
+        for x in 1..9 {
+            map.set_ceiling(x, y, 1);
+        }
+    }
+    
+    // Along the outer walls: a flickering monitor, a conveyor belt, a
+    // waterfall and a switch that toggles between off and on
+    map.set_tile(10, 0, SCREEN_TILE);
+    for y in 14..18 {
+        map.set_tile(23, y, CONVEYOR_TILE);
+    }
+    for y in 9..12 {
+        map.set_tile(0, y, WATERFALL_TILE);
+    }
+    map.set_tile(12, 0, SWITCH_OFF_TILE);
+    map.surfaces.insert(SCREEN_TILE, WallSurface::animated("screen", 4, 8.0));
+    map.surfaces.insert(CONVEYOR_TILE, WallSurface { scroll: Vec2f::new(0.5, 0.0), ..WallSurface::still("conveyor") });
+    map.surfaces.insert(WATERFALL_TILE, WallSurface { scroll: Vec2f::new(0.0, -0.8), ..WallSurface::animated("water", 3, 4.0) });
+    map.surfaces.insert(SWITCH_OFF_TILE, WallSurface::still("switch_off"));
+    map.surfaces.insert(SWITCH_ON_TILE, WallSurface::still("switch_on"));
+    map.switches.insert(SWITCH_OFF_TILE, SWITCH_ON_TILE);
+    map.switches.insert(SWITCH_ON_TILE, SWITCH_OFF_TILE);
+    
+    // An ice rink to the north-east and a mud patch south of the middle
+    for y in 2..8 {
+        for x in 17..22 {
+            map.set_floor(x, y, ICE_FLOOR);
+        }
+    }
+    for y in 17..21 {
+        for x in 11..15 {
+            map.set_floor(x, y, MUD_FLOOR);
         }
     }
+    map.floor_materials.insert(ICE_FLOOR, FloorMaterial { color: [190, 225, 240], friction: 0.1, speed: 1.0 });
+    map.floor_materials.insert(MUD_FLOOR, FloorMaterial { color: [100, 70, 40], friction: 1.5, speed: 0.45 });
     
     commands.insert_resource(map);
     info!("Game map loaded: 24x24 with walls and obstacles");
@@ -30,7 +30,7 @@ impl Default for MathConstants {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec2f {
     pub x: f32,
     pub y: f32,
@@ -49,7 +49,7 @@ impl Vec2f {
         (self.x * self.x + self.y * self.y).sqrt()
     }
     
-    pub fn _normalize(&self) -> Self {
+    pub fn normalize(&self) -> Self {
         let len = self.length();
         if len > 0.0 {
             Self {
@@ -61,7 +61,7 @@ impl Vec2f {
         }
     }
     
-    pub fn _dot(&self, other: &Vec2f) -> f32 {
+    pub fn dot(&self, other: &Vec2f) -> f32 {
         self.x * other.x + self.y * other.y
     }
     
@@ -127,7 +127,7 @@ pub fn normalize_angle(angle: f32) -> f32 {
     normalized
 }
 
-pub fn _lerp(a: f32, b: f32, t: f32) -> f32 {
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
     a + (b - a) * t
 }
 
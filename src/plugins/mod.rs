@@ -1,9 +1,25 @@
 pub mod window;
 pub mod canvas;
+pub mod draw;
 pub mod input;
 pub mod debug;
 pub mod math;
 pub mod player;
 pub mod render;
 pub mod map;
-pub mod raycast;
\ No newline at end of file
+pub mod raycast;
+pub mod gamepad;
+pub mod actions;
+pub mod replay;
+pub mod save;
+pub mod font_data;
+pub mod text;
+pub mod hud;
+pub mod raydebug;
+pub mod portal;
+pub mod segment;
+pub mod lighting;
+pub mod sky;
+pub mod palette;
+pub mod postprocess;
+pub mod surface;
\ No newline at end of file
@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use std::path::Path;
+use super::actions::{Action, ActionState};
+use super::canvas::{CanvasPass, PixelCanvas};
+use super::debug::DebugOverlayAppExt;
+
+pub const PALETTE_DIR: &str = "assets/palette";
+pub const LIGHT_LEVELS: usize = 32;
+const DISTANCE_FADE: f32 = 1.0; // Light levels lost per cell of distance
+// PLAYPAL layout: the normal palette, then increasingly red ones for
+// damage, then increasingly gold ones for pickups
+const DAMAGE_PALETTES: usize = 8;
+const PICKUP_PALETTES: usize = 4;
+const DAMAGE_COLOR: [u8; 3] = [255, 0, 0];
+const PICKUP_COLOR: [u8; 3] = [215, 186, 69];
+const DAMAGE_FADE: f32 = 1.0; // Per second
+const PICKUP_FADE: f32 = 3.0;
+// Ramps for the built-in palette, each shaded from black up to this color
+const RAMP_COLORS: [[u8; 3]; 16] = [
+    [255, 255, 255], // Grays
+    [255, 0, 0],     // Red walls
+    [0, 255, 0],     // Green walls
+    [0, 0, 255],     // Blue walls
+    [255, 255, 0],   // Yellow walls
+    [255, 0, 255],   // Magenta walls
+    [34, 139, 34],   // Floor
+    [135, 206, 235], // Sky
+    [40, 90, 180],   // Deep sky
+    [70, 100, 90],   // Hills
+    [150, 130, 110], // Wood
+    [230, 180, 140], // Skin
+    [170, 200, 220], // Mirror frames
+    [255, 170, 90],  // Torchlight
+    [120, 255, 140], // Green light
+    [128, 128, 128], // Default gray walls
+];
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(PaletteMode::load())
+            .add_event::<PaletteFlash>()
+            .add_debug_page("Palette", palette_debug_page)
+            .add_systems(Update, (
+                (toggle_palette_mode, handle_palette_flashes, fade_palette_effects)
+                    .chain()
+                    .before(CanvasPass::World),
+                resolve_palette.in_set(CanvasPass::Resolve),
+            ));
+    }
+}
+
+// Something that tints the whole screen for a moment, Doom style
+#[derive(Event, Debug, Clone, Copy)]
+pub enum PaletteFlash {
+    Damage(f32), // 0..1, how hard the hit was
+    Pickup,
+}
+
+// 256 colors plus light-level lookup tables, so shading a pixel is two
+// table lookups instead of float math
+pub struct Palette {
+    pub colors: [[u8; 3]; 256],
+    colormap: Vec<[u8; 256]>, // One per light level, 0 = full bright
+    inverse: Vec<u8>,         // Nearest index for each 5-bit-per-channel color
+}
+
+impl Palette {
+    // Without a colormap one is built by darkening every color in steps
+    pub fn new(colors: [[u8; 3]; 256], colormap: Option<Vec<[u8; 256]>>) -> Self {
+        let inverse = (0..32 * 32 * 32)
+            .map(|packed: usize| {
+                let channel = |shift: usize| ((packed >> shift) & 31) as u8 * 8 + 4;
+                nearest(&colors, [channel(10), channel(5), channel(0)])
+            })
+            .collect();
+        let mut palette = Self {
+            colors,
+            colormap: Vec::new(),
+            inverse,
+        };
+
+        palette.colormap = colormap.unwrap_or_else(|| {
+            (0..LIGHT_LEVELS)
+                .map(|level| {
+                    let brightness = 1.0 - level as f32 / LIGHT_LEVELS as f32;
+                    std::array::from_fn(|index| {
+                        let [r, g, b] = palette.colors[index].map(|channel| (channel as f32 * brightness) as u8);
+                        palette.index_of([r, g, b, 255])
+                    })
+                })
+                .collect()
+        });
+        palette
+    }
+
+    pub fn index_of(&self, color: [u8; 4]) -> u8 {
+        let packed = ((color[0] as usize >> 3) << 10) | ((color[1] as usize >> 3) << 5) | (color[2] as usize >> 3);
+        self.inverse[packed]
+    }
+
+    pub fn shade(&self, index: u8, level: usize) -> u8 {
+        self.colormap[level.min(self.colormap.len() - 1)][index as usize]
+    }
+
+    // Further away and less lit both mean darker, as in Doom
+    pub fn light_level(&self, distance: f32, light: f32) -> usize {
+        let levels = self.colormap.len() as f32;
+        let level = (1.0 - light) * levels + distance * DISTANCE_FADE;
+        level.clamp(0.0, levels - 1.0) as usize
+    }
+}
+
+fn nearest(colors: &[[u8; 3]; 256], color: [u8; 3]) -> u8 {
+    let distance = |candidate: &[u8; 3]| -> i32 {
+        (0..3).map(|channel| (candidate[channel] as i32 - color[channel] as i32).pow(2)).sum()
+    };
+    (0..=255u8).min_by_key(|&index| distance(&colors[index as usize])).unwrap_or(0)
+}
+
+#[derive(Resource)]
+pub struct PaletteMode {
+    pub enabled: bool,
+    pub palette: Palette,
+    pub effects: Vec<[[u8; 3]; 256]>, // PLAYPAL order, the first is the normal palette
+    pub damage: f32,                  // Current strength of each effect, fading to 0
+    pub pickup: f32,
+}
+
+impl PaletteMode {
+    // assets/palette/PLAYPAL and COLORMAP in Doom's raw layout; either can
+    // be missing, in which case the built-in ramps and effects are used
+    pub fn load() -> Self {
+        let directory = Path::new(PALETTE_DIR);
+        let mut effects = std::fs::read(directory.join("PLAYPAL"))
+            .map(|data| data.chunks_exact(768).map(read_palette).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if effects.is_empty() {
+            effects.push(generate_palette());
+        } else {
+            info!("Palette loaded: {} palettes", effects.len());
+        }
+
+        let base = effects[0];
+        for index in effects.len()..1 + DAMAGE_PALETTES + PICKUP_PALETTES {
+            effects.push(if index <= DAMAGE_PALETTES {
+                blend_palette(&base, DAMAGE_COLOR, index as f32 / (DAMAGE_PALETTES + 1) as f32)
+            } else {
+                blend_palette(&base, PICKUP_COLOR, (index - DAMAGE_PALETTES) as f32 / (2 * PICKUP_PALETTES) as f32)
+            });
+        }
+
+        // Doom's COLORMAP has extra tables after the light levels; only
+        // the light levels are used
+        let colormap = std::fs::read(directory.join("COLORMAP")).ok()
+            .map(|data| {
+                data.chunks_exact(256)
+                    .take(LIGHT_LEVELS)
+                    .map(|table| std::array::from_fn(|index| table[index]))
+                    .collect::<Vec<[u8; 256]>>()
+            })
+            .filter(|tables| !tables.is_empty());
+
+        Self {
+            enabled: false,
+            palette: Palette::new(base, colormap),
+            effects,
+            damage: 0.0,
+            pickup: 0.0,
+        }
+    }
+
+    // Damage wins over pickups, like in Doom
+    pub fn active_effect(&self) -> usize {
+        if self.damage > 0.0 {
+            ((self.damage * DAMAGE_PALETTES as f32).ceil() as usize).clamp(1, DAMAGE_PALETTES)
+        } else if self.pickup > 0.0 {
+            DAMAGE_PALETTES + ((self.pickup * PICKUP_PALETTES as f32).ceil() as usize).clamp(1, PICKUP_PALETTES)
+        } else {
+            0
+        }
+    }
+}
+
+fn read_palette(data: &[u8]) -> [[u8; 3]; 256] {
+    std::array::from_fn(|index| [data[index * 3], data[index * 3 + 1], data[index * 3 + 2]])
+}
+
+// Sixteen ramps of sixteen shades, covering the colors the world uses
+fn generate_palette() -> [[u8; 3]; 256] {
+    std::array::from_fn(|index| {
+        let (ramp, shade) = (index / 16, index % 16);
+        let brightness = shade as f32 / 15.0;
+        RAMP_COLORS[ramp].map(|channel| (channel as f32 * brightness) as u8)
+    })
+}
+
+fn blend_palette(base: &[[u8; 3]; 256], target: [u8; 3], amount: f32) -> [[u8; 3]; 256] {
+    base.map(|color| std::array::from_fn(|channel| {
+        (color[channel] as f32 + (target[channel] as f32 - color[channel] as f32) * amount) as u8
+    }))
+}
+
+fn toggle_palette_mode(
+    actions: Res<ActionState>,
+    mut mode: ResMut<PaletteMode>,
+    mut canvas: ResMut<PixelCanvas>,
+) {
+    if actions.just_pressed(Action::TogglePaletteMode) {
+        mode.enabled = !mode.enabled;
+        canvas.set_indexed(mode.enabled);
+        info!("Palette mode: {}", if mode.enabled { "on" } else { "off" });
+    }
+}
+
+fn handle_palette_flashes(
+    mut flashes: EventReader<PaletteFlash>,
+    mut mode: ResMut<PaletteMode>,
+) {
+    for flash in flashes.read() {
+        match *flash {
+            PaletteFlash::Damage(amount) => mode.damage = (mode.damage + amount).min(1.0),
+            PaletteFlash::Pickup => mode.pickup = 1.0,
+        }
+    }
+}
+
+fn fade_palette_effects(
+    mut mode: ResMut<PaletteMode>,
+    time: Res<Time>,
+) {
+    if mode.damage <= 0.0 && mode.pickup <= 0.0 {
+        return;
+    }
+    let dt = time.delta_secs();
+    mode.damage = (mode.damage - DAMAGE_FADE * dt).max(0.0);
+    mode.pickup = (mode.pickup - PICKUP_FADE * dt).max(0.0);
+}
+
+// The effects only swap which palette the indices are looked up in, so
+// they cost nothing per pixel
+fn resolve_palette(
+    mode: Res<PaletteMode>,
+    mut canvas: ResMut<PixelCanvas>,
+) {
+    if mode.enabled {
+        canvas.resolve_indices(&mode.effects[mode.active_effect()]);
+    }
+}
+
+fn palette_debug_page(world: &World) -> Vec<String> {
+    let mode = world.resource::<PaletteMode>();
+    vec![
+        format!("Palette mode: {}", if mode.enabled { "on" } else { "off" }),
+        format!("Palettes: {}", mode.effects.len()),
+        format!("Light levels: {}", mode.palette.colormap.len()),
+        format!("Effect: {} (damage {:.2}, pickup {:.2})", mode.active_effect(), mode.damage, mode.pickup),
+    ]
+}
@@ -1,34 +1,145 @@
 use bevy::prelude::*;
 use bevy::input::mouse::MouseMotion;
 use bevy::window::CursorGrabMode;
-use super::math::{Vec2f, normalize_angle};
-use super::map::GameMap;
+use super::math::{Vec2f, normalize_angle, lerp};
+use super::map::{FloorMaterial, GameMap};
+use super::portal::step_through_portal;
+use super::actions::{Action, ActionState, ActionSystem};
+use super::debug::DebugOverlayAppExt;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
+        let settings = SimulationSettings::default();
         app
+            .insert_resource(Time::<Fixed>::from_hz(settings.tick_rate))
+            .insert_resource(settings)
+            .insert_resource(PlayerInput::default())
+            .insert_resource(StepInput::default())
+            .insert_resource(PlayerView::default())
+            .add_event::<Footstep>()
+            .add_debug_page("Player", player_debug_page)
+            .configure_sets(PreUpdate, PlayerSystems::GatherInput.after(ActionSystem))
+            .configure_sets(FixedUpdate, (PlayerSystems::PrepareStep, PlayerSystems::Simulate).chain())
             .add_systems(Startup, setup_player)
+            .add_systems(PreUpdate, (
+                reset_player_input.before(PlayerSystems::GatherInput),
+                (gather_keyboard_input, gather_mouse_look).in_set(PlayerSystems::GatherInput),
+            ))
+            .add_systems(FixedUpdate, (
+                prepare_step.in_set(PlayerSystems::PrepareStep),
+                apply_step.in_set(PlayerSystems::Simulate),
+            ))
             .add_systems(Update, (
+                apply_simulation_settings,
                 handle_mouse_capture,
-                handle_mouse_look,
-                handle_movement,
+                toggle_pitch_limit,
                 update_player_direction,
+                update_player_view.in_set(PlayerSystems::Interpolate),
             ));
     }
 }
 
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+// Eye heights are in wall heights, so 0.5 looks at the middle of a wall
+pub const STAND_EYE_HEIGHT: f32 = 0.5;
+pub const CROUCH_EYE_HEIGHT: f32 = 0.3;
+const CROUCH_SPEED: f32 = 6.0;      // Stance change per second, 1 = all the way down
+const CROUCH_MOVE_SCALE: f32 = 0.5; // Walking speed while fully crouched
+// Movement speeds are in cells per second, acceleration in cells per
+// second squared, both on plain ground; floor friction scales them
+const ACCELERATION: f32 = 20.0;     // Towards the wanted velocity while pushing
+const FRICTION: f32 = 12.0;         // Towards a stop when not pushing
+const AIR_CONTROL: f32 = 0.2;       // Grip left while off the ground
+const SPRINT_SCALE: f32 = 1.7;
+const STAMINA_DRAIN: f32 = 0.25;    // Per second of sprinting, out of 1
+const STAMINA_RECOVERY: f32 = 0.15;
+const STAMINA_RESTART: f32 = 0.3;   // Needed to sprint again after running out
+const FOOTSTEP_MIN_SPEED: f32 = 0.5;
+const JUMP_SPEED: f32 = 2.2;        // Wall heights per second, straight up
+const GRAVITY: f32 = 9.0;
+const BOB_STRIDE: f32 = 0.8;        // Distance walked per bob cycle
+const BOB_AMPLITUDE: f32 = 0.015;
+// Pitch is in radians, positive looking up. Looking is y-shearing: the
+// horizon slides by tan(pitch) projection distances (one screen height),
+// so atan(0.5) is as far as it goes before the horizon leaves the screen.
+pub const MAX_PITCH: f32 = 0.4636;
+// Shearing stretches everything near the top and bottom edges more the
+// further it goes; about 15 degrees keeps that hard to notice
+pub const SHEAR_LIMITED_PITCH: f32 = 0.26;
+
+#[derive(Resource)]
+pub struct SimulationSettings {
+    pub tick_rate: f64, // Simulation steps per second, independent of frame rate
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            tick_rate: DEFAULT_TICK_RATE,
+        }
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlayerSystems {
+    GatherInput, // PreUpdate: input sources add into PlayerInput
+    PrepareStep, // FixedUpdate: PlayerInput is turned into this tick's StepInput
+    Simulate,    // FixedUpdate: StepInput is applied to Player
+    Interpolate, // Update: PlayerView is blended between the last two ticks
+}
+
+// Input for one simulation step. Between ticks, the movement axes hold this
+// frame's state and the look deltas accumulate until a tick consumes them.
+// Replays record and substitute StepInput, so this must contain every
+// input the simulation reads.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub struct PlayerInput {
+    pub dt: f32,
+    pub forward: f32,    // -1..1, analog sticks give partial values
+    pub strafe: f32,     // -1..1, positive is right
+    pub turn: f32,       // -1..1, scaled by rotation_speed
+    pub look_yaw: f32,   // Radians, already scaled by sensitivity
+    pub look_pitch: f32, // Radians, positive looks up
+    pub jump: bool,
+    pub crouch: bool,
+    pub sprint: bool,
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct StepInput(pub PlayerInput);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerPose {
+    pub position: Vec2f,
+    pub angle: f32,
+    pub pitch: f32,
+}
+
 #[derive(Resource)]
 pub struct Player {
     pub position: Vec2f,
     pub angle: f32,      // Horizontal angle (yaw)
-    pub pitch: f32,      // Vertical angle (pitch)
+    pub pitch: f32,      // Vertical angle in radians, within +-max_pitch()
     pub direction: Vec2f,
     pub plane: Vec2f,
-    pub move_speed: f32,
+    pub move_speed: f32,     // Top walking speed
     pub rotation_speed: f32,
     pub mouse_sensitivity: f32,
+    pub eye_height: f32,     // Camera height above the floor, from all of the below
+    pub jump_height: f32,    // Feet above the floor
+    pub vertical_speed: f32,
+    pub crouch: f32,         // 0 = standing, 1 = fully crouched
+    pub bob_phase: f32,      // Radians into the walk cycle
+    pub bob_amount: f32,     // Eases in and out as walking starts and stops
+    pub head_bob: f32,       // Scale of the bob, 0 turns it off
+    pub limit_shear: bool,   // Keep pitch to SHEAR_LIMITED_PITCH
+    pub velocity: Vec2f,     // Cells per second
+    pub stamina: f32,        // 0..1, spent by sprinting
+    pub winded: bool,        // Ran out of stamina and can't sprint until it recovers
+    pub sprinting: bool,
+    pub footsteps: u32,      // Steps taken so far, each one a Footstep event
 }
 
 impl Default for Player {
@@ -43,29 +154,204 @@ impl Default for Player {
             move_speed: 3.0,
             rotation_speed: 3.0,
             mouse_sensitivity: 0.003,
+            eye_height: STAND_EYE_HEIGHT,
+            jump_height: 0.0,
+            vertical_speed: 0.0,
+            crouch: 0.0,
+            bob_phase: 0.0,
+            bob_amount: 0.0,
+            head_bob: 1.0,
+            limit_shear: false,
+            velocity: Vec2f::zero(),
+            stamina: 1.0,
+            winded: false,
+            sprinting: false,
+            footsteps: 0,
         }
     }
 }
 
+impl Player {
+    pub fn max_pitch(&self) -> f32 {
+        if self.limit_shear { SHEAR_LIMITED_PITCH } else { MAX_PITCH }
+    }
+
+    pub fn pose(&self) -> PlayerPose {
+        PlayerPose {
+            position: self.position,
+            angle: self.angle,
+            pitch: self.pitch,
+        }
+    }
+    
+    // Poses don't include jumping, crouching or momentum, so the player
+    // lands standing still and rested; replays rely on this to start from
+    // the same state
+    pub fn set_pose(&mut self, pose: PlayerPose) {
+        self.position = pose.position;
+        self.angle = normalize_angle(pose.angle);
+        self.pitch = pose.pitch.clamp(-self.max_pitch(), self.max_pitch());
+        self.direction = Vec2f::from_angle(self.angle);
+        self.plane = Vec2f::from_angle(self.angle + std::f32::consts::PI / 2.0) * 0.66;
+        self.eye_height = STAND_EYE_HEIGHT;
+        self.jump_height = 0.0;
+        self.vertical_speed = 0.0;
+        self.crouch = 0.0;
+        self.bob_phase = 0.0;
+        self.bob_amount = 0.0;
+        self.velocity = Vec2f::zero();
+        self.stamina = 1.0;
+        self.winded = false;
+        self.sprinting = false;
+    }
+}
+
+// A foot coming down, for sounds and effects; sent from the simulation so
+// replays step in the same places
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Footstep {
+    pub position: Vec2f,
+    pub floor: u8,  // Floor type underfoot
+    pub speed: f32, // Cells per second when it landed
+}
+
+// Pose used for rendering, interpolated between the previous and the
+// current simulation tick so motion stays smooth at any refresh rate
+#[derive(Resource)]
+pub struct PlayerView {
+    pub position: Vec2f,
+    pub angle: f32,
+    pub pitch: f32,
+    pub direction: Vec2f,
+    pub plane: Vec2f,
+    pub eye_height: f32,
+    pub previous: PlayerPose,
+    pub previous_eye_height: f32,
+}
+
+impl Default for PlayerView {
+    fn default() -> Self {
+        let player = Player::default();
+        Self {
+            position: player.position,
+            angle: player.angle,
+            pitch: player.pitch,
+            direction: player.direction,
+            plane: player.plane,
+            eye_height: player.eye_height,
+            previous: player.pose(),
+            previous_eye_height: player.eye_height,
+        }
+    }
+}
+
+impl PlayerView {
+    // Skip interpolation after a teleport such as a replay start or a load
+    pub fn snap_to(&mut self, pose: PlayerPose) {
+        self.previous = pose;
+    }
+}
+
+fn player_debug_page(world: &World) -> Vec<String> {
+    let player = world.resource::<Player>();
+    let tile = world.get_resource::<GameMap>()
+        .map(|map| map.get_tile(player.position.x as usize, player.position.y as usize));
+    
+    vec![
+        format!("Position: ({:.3}, {:.3})", player.position.x, player.position.y),
+        format!("Tile: ({}, {}) type {}", player.position.x as i32, player.position.y as i32,
+            tile.map_or("-".to_string(), |tile| tile.to_string())),
+        format!("Angle: {:.3} rad ({:.1} deg)", player.angle, player.angle.to_degrees()),
+        format!("Pitch: {:.3} rad ({:.1} deg, limit {:.1}{})", player.pitch, player.pitch.to_degrees(),
+            player.max_pitch().to_degrees(), if player.limit_shear { ", shear limited" } else { "" }),
+        format!("Eye height: {:.3} (jump {:.3}, crouch {:.2})", player.eye_height, player.jump_height, player.crouch),
+        format!("Speed: {:.2} ({:.2}, {:.2}){}", player.velocity.length(), player.velocity.x, player.velocity.y,
+            if player.sprinting { " sprinting" } else { "" }),
+        format!("Stamina: {:.2}{}", player.stamina, if player.winded { " (winded)" } else { "" }),
+        format!("Footsteps: {}", player.footsteps),
+    ]
+}
+
 fn setup_player(mut commands: Commands) {
     commands.insert_resource(Player::default());
     info!("Player initialized at position (12.0, 12.0) - center of map");
     info!("Click window to capture mouse for FPS controls");
 }
 
+// Returns true when the move went through a portal. Momentum turns with
+// the portal and is lost against walls.
+pub fn try_move_player(player: &mut Player, map: Option<&GameMap>, offset: Vec2f) -> bool {
+    let next_pos = player.position + offset;
+    let Some(map) = map else {
+        player.position = next_pos;
+        return false;
+    };
+    
+    if let Some(transit) = step_through_portal(map, player.position, offset) {
+        player.position = map.wrap_position(transit.position);
+        player.angle = normalize_angle(player.angle + transit.rotation);
+        player.velocity = player.velocity.rotate(transit.rotation);
+        player.direction = Vec2f::from_angle(player.angle);
+        player.plane = Vec2f::from_angle(player.angle + std::f32::consts::PI / 2.0) * 0.66;
+        return true;
+    }
+    
+    if map.is_valid_position(next_pos) && !map.segment_blocks(player.position, next_pos) {
+        player.position = map.wrap_position(next_pos);
+    } else {
+        player.velocity = Vec2f::zero();
+    }
+    false
+}
+
+fn apply_simulation_settings(
+    settings: Res<SimulationSettings>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    if settings.is_changed() && !settings.is_added() {
+        fixed_time.set_timestep_hz(settings.tick_rate);
+        info!("Simulation tick rate: {} Hz", settings.tick_rate);
+    }
+}
+
+fn reset_player_input(
+    mut input: ResMut<PlayerInput>,
+    time: Res<Time>,
+) {
+    // Look deltas are kept until a fixed tick consumes them
+    input.dt = time.delta_secs();
+    input.forward = 0.0;
+    input.strafe = 0.0;
+    input.turn = 0.0;
+    input.jump = false;
+    input.crouch = false;
+    input.sprint = false;
+}
+
+fn toggle_pitch_limit(
+    actions: Res<ActionState>,
+    mut player: ResMut<Player>,
+) {
+    if actions.just_pressed(Action::LimitPitch) {
+        player.limit_shear = !player.limit_shear;
+        let limit = player.max_pitch();
+        player.pitch = player.pitch.clamp(-limit, limit);
+        info!("Pitch limit: {:.0} degrees", limit.to_degrees());
+    }
+}
+
 fn handle_mouse_capture(
     mut windows: Query<&mut Window>,
-    mouse_button: Res<ButtonInput<MouseButton>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
 ) {
     if let Ok(mut window) = windows.single_mut() {
-        if mouse_button.just_pressed(MouseButton::Left) {
+        if actions.just_pressed(Action::CaptureMouse) {
             window.cursor_options.grab_mode = CursorGrabMode::Locked;
             window.cursor_options.visible = false;
             info!("Mouse captured - WASD to move, mouse to look, ESC to release");
         }
         
-        if keyboard_input.just_pressed(KeyCode::Escape) {
+        if actions.just_pressed(Action::ReleaseMouse) {
             window.cursor_options.grab_mode = CursorGrabMode::None;
             window.cursor_options.visible = true;
             info!("Mouse released - click to recapture");
@@ -73,97 +359,217 @@ fn handle_mouse_capture(
     }
 }
 
-fn handle_mouse_look(
-    mut player: ResMut<Player>,
+fn gather_keyboard_input(
+    actions: Res<ActionState>,
+    mut input: ResMut<PlayerInput>,
+) {
+    let axis = |positive: Action, negative: Action| {
+        let mut value = 0.0;
+        if actions.pressed(positive) {
+            value += 1.0;
+        }
+        if actions.pressed(negative) {
+            value -= 1.0;
+        }
+        value
+    };
+    
+    input.forward += axis(Action::MoveForward, Action::MoveBackward);
+    input.strafe += axis(Action::StrafeRight, Action::StrafeLeft);
+    input.turn += axis(Action::TurnRight, Action::TurnLeft);
+    input.jump |= actions.pressed(Action::Jump);
+    input.crouch |= actions.pressed(Action::Crouch);
+    input.sprint |= actions.pressed(Action::Sprint);
+}
+
+fn gather_mouse_look(
+    player: Res<Player>,
+    mut input: ResMut<PlayerInput>,
     mut mouse_motion: EventReader<MouseMotion>,
 ) {
     for event in mouse_motion.read() {
-        let delta_x = event.delta.x;
-        let delta_y = event.delta.y;
-        
-        // Horizontal rotation (yaw)
-        player.angle += delta_x * player.mouse_sensitivity;
-        player.angle = normalize_angle(player.angle);
-        
-        // Vertical rotation (pitch) - clamp to prevent over-rotation
-        player.pitch -= delta_y * player.mouse_sensitivity;
-        player.pitch = player.pitch.clamp(-4.0, 4.0); // Limit pitch to prevent extreme values
-        
-        // Update direction vectors
-        player.direction = Vec2f::from_angle(player.angle);
-        player.plane = Vec2f::from_angle(player.angle + std::f32::consts::PI / 2.0) * 0.66;
+        input.look_yaw += event.delta.x * player.mouse_sensitivity;
+        input.look_pitch -= event.delta.y * player.mouse_sensitivity;
     }
 }
 
-fn handle_movement(
-    mut player: ResMut<Player>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+fn prepare_step(
+    mut input: ResMut<PlayerInput>,
+    mut step: ResMut<StepInput>,
+    mut view: ResMut<PlayerView>,
+    player: Res<Player>,
     time: Res<Time>,
+) {
+    step.0 = PlayerInput {
+        dt: time.delta_secs(),
+        look_yaw: std::mem::take(&mut input.look_yaw),
+        look_pitch: std::mem::take(&mut input.look_pitch),
+        ..*input
+    };
+    view.previous = player.pose();
+    view.previous_eye_height = player.eye_height;
+}
+
+fn apply_step(
+    mut player: ResMut<Player>,
+    mut view: ResMut<PlayerView>,
+    step: Res<StepInput>,
     map: Option<Res<GameMap>>,
+    mut footsteps: EventWriter<Footstep>,
 ) {
-    let move_speed = player.move_speed * time.delta_secs();
-    let mut new_position = player.position;
-    
-    if keyboard_input.pressed(KeyCode::KeyW) {
-        let next_pos = player.position + player.direction * move_speed;
-        if let Some(ref map) = map {
-            if map.is_valid_position(next_pos) {
-                new_position = next_pos;
-            }
-        } else {
-            new_position = next_pos;
-        }
+    let steps = player.footsteps;
+    if simulate_player(&mut player, map.as_deref(), &step) {
+        view.snap_to(player.pose()); // Don't interpolate across a portal
     }
+    if player.footsteps != steps {
+        let floor = map.as_deref().map_or(0, |map| map.floor_at(player.position.x.floor() as i32, player.position.y.floor() as i32));
+        footsteps.write(Footstep {
+            position: player.position,
+            floor,
+            speed: player.velocity.length(),
+        });
+    }
+}
+
+// One simulation step, shared by live play and replays so both produce
+// exactly the same trajectory for the same inputs. Returns true when the
+// player was teleported by a portal.
+pub fn simulate_player(player: &mut Player, map: Option<&GameMap>, input: &PlayerInput) -> bool {
+    player.angle += input.turn.clamp(-1.0, 1.0) * player.rotation_speed * input.dt + input.look_yaw;
+    player.angle = normalize_angle(player.angle);
     
-    if keyboard_input.pressed(KeyCode::KeyS) {
-        let next_pos = player.position - player.direction * move_speed;
-        if let Some(ref map) = map {
-            if map.is_valid_position(next_pos) {
-                new_position = next_pos;
-            }
-        } else {
-            new_position = next_pos;
-        }
+    player.pitch = (player.pitch + input.look_pitch).clamp(-player.max_pitch(), player.max_pitch());
+    
+    player.direction = Vec2f::from_angle(player.angle);
+    player.plane = Vec2f::from_angle(player.angle + std::f32::consts::PI / 2.0) * 0.66;
+    
+    // Analog input scales speed; combined keys must not move faster diagonally
+    let mut movement = Vec2f::new(input.forward, input.strafe);
+    if movement.length() > 1.0 {
+        movement = movement.normalize();
     }
+    update_stamina(player, input, movement.x);
     
-    if keyboard_input.pressed(KeyCode::KeyA) {
-        let left_dir = player.direction.rotate(-std::f32::consts::PI / 2.0);
-        let next_pos = player.position + left_dir * move_speed;
-        if let Some(ref map) = map {
-            if map.is_valid_position(next_pos) {
-                new_position = next_pos;
-            }
-        } else {
-            new_position = next_pos;
-        }
+    let floor = map.map_or(FloorMaterial::GROUND, |map| map.floor_material(player.position));
+    let sprint = if player.sprinting { SPRINT_SCALE } else { 1.0 };
+    let top_speed = player.move_speed * lerp(1.0, CROUCH_MOVE_SCALE, player.crouch) * sprint * floor.speed;
+    let right_dir = player.direction.rotate(std::f32::consts::PI / 2.0);
+    let wish = (player.direction * movement.x + right_dir * movement.y) * top_speed;
+    let grip = floor.friction * if player.jump_height > 0.0 { AIR_CONTROL } else { 1.0 };
+    player.velocity = accelerate(player.velocity, wish, grip, input.dt);
+    
+    update_eye_height(player, input);
+    if player.velocity.length() < 0.001 {
+        player.velocity = Vec2f::zero();
+        return false;
     }
     
-    if keyboard_input.pressed(KeyCode::KeyD) {
-        let right_dir = player.direction.rotate(std::f32::consts::PI / 2.0);
-        let next_pos = player.position + right_dir * move_speed;
-        if let Some(ref map) = map {
-            if map.is_valid_position(next_pos) {
-                new_position = next_pos;
-            }
-        } else {
-            new_position = next_pos;
+    let offset = player.velocity * input.dt;
+    try_move_player(player, map, offset)
+}
+
+// Velocity chases the velocity the input asks for, hard while pushing and
+// more gently when coasting to a stop. Grip scales both, so ice is slow
+// to get going and slow to stop.
+fn accelerate(velocity: Vec2f, wish: Vec2f, grip: f32, dt: f32) -> Vec2f {
+    let rate = if wish.length() > 0.0 { ACCELERATION } else { FRICTION };
+    let max_change = rate * grip * dt;
+    let change = wish - velocity;
+    let length = change.length();
+    if length <= max_change {
+        wish
+    } else {
+        velocity + change * (max_change / length)
+    }
+}
+
+// Sprinting only goes forwards and standing up. Running out of stamina
+// stops it until enough has come back, so it can't be feathered at zero.
+fn update_stamina(player: &mut Player, input: &PlayerInput, forward: f32) {
+    let dt = input.dt;
+    player.sprinting = input.sprint && forward > 0.0 && player.crouch < 0.5 && !player.winded;
+    if player.sprinting {
+        player.stamina = (player.stamina - STAMINA_DRAIN * dt).max(0.0);
+        player.winded = player.stamina <= 0.0;
+    } else {
+        player.stamina = (player.stamina + STAMINA_RECOVERY * dt).min(1.0);
+        if player.winded && player.stamina >= STAMINA_RESTART {
+            player.winded = false;
         }
     }
-    
-    if keyboard_input.pressed(KeyCode::ArrowLeft) {
-        player.angle -= player.rotation_speed * time.delta_secs();
-        player.angle = normalize_angle(player.angle);
+}
+
+// Jumping, crouching and head bob all come down to how high the eye is.
+// The bob and footsteps follow how fast the player is actually going.
+fn update_eye_height(player: &mut Player, input: &PlayerInput) {
+    let dt = input.dt;
+    let on_ground = player.jump_height <= 0.0;
+    if input.jump && on_ground && player.crouch < 0.5 {
+        player.vertical_speed = JUMP_SPEED;
     }
+    if player.vertical_speed > 0.0 || !on_ground {
+        player.vertical_speed -= GRAVITY * dt;
+        player.jump_height += player.vertical_speed * dt;
+        if player.jump_height <= 0.0 {
+            player.jump_height = 0.0;
+            player.vertical_speed = 0.0;
+        }
+    }
+    
+    let stance = if input.crouch { 1.0 } else { 0.0 };
+    player.crouch += (stance - player.crouch).clamp(-CROUCH_SPEED * dt, CROUCH_SPEED * dt);
     
-    if keyboard_input.pressed(KeyCode::ArrowRight) {
-        player.angle += player.rotation_speed * time.delta_secs();
-        player.angle = normalize_angle(player.angle);
+    // The bob follows distance covered, and fades out in the air. A foot
+    // comes down every half cycle, and on landing.
+    let speed = player.velocity.length();
+    let walking = (speed / player.move_speed.max(0.001)).min(1.0);
+    let bob_target = if on_ground { walking } else { 0.0 };
+    player.bob_amount += (bob_target - player.bob_amount).clamp(-4.0 * dt, 4.0 * dt);
+    let phase = player.bob_phase + speed * dt / BOB_STRIDE * std::f32::consts::TAU;
+    let stepped = on_ground && speed >= FOOTSTEP_MIN_SPEED
+        && (phase / std::f32::consts::PI).floor() > (player.bob_phase / std::f32::consts::PI).floor();
+    let landed = !on_ground && player.jump_height <= 0.0;
+    if stepped || landed {
+        player.footsteps = player.footsteps.wrapping_add(1);
     }
+    player.bob_phase = phase.rem_euclid(std::f32::consts::TAU);
+    let bob = player.bob_phase.sin() * BOB_AMPLITUDE * player.bob_amount * player.head_bob;
+    
+    player.eye_height = lerp(STAND_EYE_HEIGHT, CROUCH_EYE_HEIGHT, player.crouch) + player.jump_height + bob;
+}
+
+fn update_player_view(
+    player: Res<Player>,
+    mut view: ResMut<PlayerView>,
+    map: Option<Res<GameMap>>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    interpolate_view(&mut view, &player, map.as_deref(), fixed_time.overstep_fraction());
+}
+
+// Blends the view from the previous tick's pose towards the current one;
+// t is how far the frame is into the next tick, 0..1
+pub fn interpolate_view(view: &mut PlayerView, player: &Player, map: Option<&GameMap>, t: f32) {
+    let previous = view.previous;
     
-    if (new_position.x - player.position.x).abs() > 0.001 || 
-       (new_position.y - player.position.y).abs() > 0.001 {
-        player.position = new_position;
+    // Blend yaw along the shortest arc so turning through 0 doesn't spin
+    let mut yaw_delta = player.angle - previous.angle;
+    if yaw_delta > std::f32::consts::PI {
+        yaw_delta -= 2.0 * std::f32::consts::PI;
+    } else if yaw_delta < -std::f32::consts::PI {
+        yaw_delta += 2.0 * std::f32::consts::PI;
     }
+    
+    // Crossing the seam of a wrapping map moves a short way, not across the map
+    view.position = match map {
+        Some(map) => map.wrap_position(previous.position + map.shortest_offset(previous.position, player.position) * t),
+        None => previous.position + (player.position - previous.position) * t,
+    };
+    view.angle = normalize_angle(previous.angle + yaw_delta * t);
+    view.pitch = lerp(previous.pitch, player.pitch, t);
+    view.eye_height = lerp(view.previous_eye_height, player.eye_height, t);
+    view.direction = Vec2f::from_angle(view.angle);
+    view.plane = Vec2f::from_angle(view.angle + std::f32::consts::PI / 2.0) * 0.66;
 }
 
 fn update_player_direction(
@@ -173,4 +579,98 @@ fn update_player_direction(
         player.direction = Vec2f::from_angle(player.angle);
         player.plane = Vec2f::from_angle(player.angle + std::f32::consts::PI / 2.0) * 0.66;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const TICK: Duration = Duration::from_nanos(16_666_667);
+
+    fn held_keys() -> PlayerInput {
+        PlayerInput { forward: 1.0, strafe: 0.3, turn: 0.5, sprint: true, ..default() }
+    }
+
+    // The fixed-step loop as Bevy runs it: frame time accumulates and every
+    // whole tick runs one simulation step of exactly the tick length
+    fn run_frames(frames: &[Duration]) -> (Player, u32) {
+        let mut player = Player::default();
+        let mut overstep = Duration::ZERO;
+        let mut ticks = 0;
+        for &delta in frames {
+            overstep += delta;
+            while overstep >= TICK {
+                overstep -= TICK;
+                let step = PlayerInput { dt: TICK.as_secs_f32(), jump: ticks == 0, ..held_keys() };
+                simulate_player(&mut player, None, &step);
+                ticks += 1;
+            }
+        }
+        (player, ticks)
+    }
+
+    // Frames of the given lengths in microseconds, repeated up to 2.5 seconds
+    fn frames(pattern: &[u64]) -> Vec<Duration> {
+        let total = Duration::from_millis(2500);
+        let mut frames = Vec::new();
+        let mut elapsed = Duration::ZERO;
+        for &micros in pattern.iter().cycle() {
+            let frame = Duration::from_micros(micros).min(total - elapsed);
+            frames.push(frame);
+            elapsed += frame;
+            if elapsed == total {
+                break;
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn simulation_does_not_depend_on_frame_rate() {
+        let (reference, ticks) = run_frames(&frames(&[16_667]));
+        assert!(reference.jump_height > 0.0 || reference.footsteps > 0);
+        for pattern in [&[33_333][..], &[6_944], &[8_000, 41_000, 2_500, 16_000], &[100_000, 1_000]] {
+            let (player, pattern_ticks) = run_frames(&frames(pattern));
+            assert_eq!(pattern_ticks, ticks, "{:?}", pattern);
+            assert_eq!(player.pose(), reference.pose(), "{:?}", pattern);
+            assert_eq!(player.velocity, reference.velocity, "{:?}", pattern);
+            assert_eq!(player.stamina, reference.stamina, "{:?}", pattern);
+        }
+
+        // Stepping once per frame with the frame's own delta drifts instead
+        let mut variable = Player::default();
+        for (frame, delta) in frames(&[8_000, 41_000, 2_500, 16_000]).into_iter().enumerate() {
+            let step = PlayerInput { dt: delta.as_secs_f32(), jump: frame == 0, ..held_keys() };
+            simulate_player(&mut variable, None, &step);
+        }
+        assert_ne!(variable.pose(), reference.pose());
+    }
+
+    #[test]
+    fn view_blends_between_ticks() {
+        let mut player = Player::default();
+        let previous = PlayerPose { position: Vec2f::new(2.0, 3.0), angle: 6.2, pitch: -0.2 };
+        player.set_pose(PlayerPose { position: Vec2f::new(3.0, 5.0), angle: 0.1, pitch: 0.2 });
+        let mut view = PlayerView::default();
+        view.snap_to(previous);
+        view.previous_eye_height = STAND_EYE_HEIGHT;
+
+        interpolate_view(&mut view, &player, None, 0.0);
+        assert_eq!(view.position, previous.position);
+        assert_eq!(view.pitch, previous.pitch);
+
+        interpolate_view(&mut view, &player, None, 1.0);
+        assert!((view.position - player.position).length() < 0.0001);
+        assert!((view.pitch - player.pitch).abs() < 0.0001);
+
+        // Halfway, and turning the short way through 0 rather than back round
+        interpolate_view(&mut view, &player, None, 0.5);
+        assert!((view.position - Vec2f::new(2.5, 4.0)).length() < 0.0001);
+        assert!(view.pitch.abs() < 0.0001);
+        let halfway = normalize_angle(6.2 + (0.1 + std::f32::consts::TAU - 6.2) / 2.0);
+        assert!((view.angle - halfway).abs() < 0.0001, "{} vs {}", view.angle, halfway);
+        assert!((view.direction - Vec2f::from_angle(halfway)).length() < 0.0001);
+    }
+}
+
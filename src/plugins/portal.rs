@@ -0,0 +1,154 @@
+use std::f32::consts::PI;
+use super::map::GameMap;
+use super::math::Vec2f;
+use super::raycast::{cast_ray_with, RayStep};
+
+// Segments a ray may follow before a portal is drawn as a plain wall
+pub const MAX_PORTAL_DEPTH: u32 = 8;
+// Exits are pushed this far off the face so they start in the cell in front of it
+pub const PORTAL_EXIT_NUDGE: f32 = 0.0001;
+
+// One side of a map cell; North is towards -y
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Face {
+    // Direction of the outward normal
+    pub fn angle(self) -> f32 {
+        match self {
+            Face::East => 0.0,
+            Face::South => PI / 2.0,
+            Face::West => PI,
+            Face::North => -PI / 2.0,
+        }
+    }
+
+    pub fn normal(self) -> Vec2f {
+        match self {
+            Face::North => Vec2f::new(0.0, -1.0),
+            Face::East => Vec2f::new(1.0, 0.0),
+            Face::South => Vec2f::new(0.0, 1.0),
+            Face::West => Vec2f::new(-1.0, 0.0),
+        }
+    }
+
+    // The face whose normal is closest to a direction
+    pub fn nearest(direction: Vec2f) -> Face {
+        if direction.x.abs() > direction.y.abs() {
+            if direction.x > 0.0 { Face::East } else { Face::West }
+        } else if direction.y > 0.0 {
+            Face::South
+        } else {
+            Face::North
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Face::North => "north",
+            Face::East => "east",
+            Face::South => "south",
+            Face::West => "west",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Face> {
+        [Face::North, Face::East, Face::South, Face::West]
+            .into_iter()
+            .find(|face| face.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortalFace {
+    pub x: i32,
+    pub y: i32,
+    pub face: Face,
+}
+
+impl PortalFace {
+    pub fn new(x: i32, y: i32, face: Face) -> Self {
+        Self { x, y, face }
+    }
+
+    pub fn center(&self) -> Vec2f {
+        Vec2f::new(self.x as f32 + 0.5, self.y as f32 + 0.5) + self.face.normal() * 0.5
+    }
+}
+
+// Two faces that see and lead into each other, in both directions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortalLink {
+    pub a: PortalFace,
+    pub b: PortalFace,
+}
+
+// Maps points and directions entering one face onto the other. It is a
+// rotation, never a mirror, so left stays left on the way through.
+pub struct PortalTransform {
+    from_center: Vec2f,
+    to_center: Vec2f,
+    pub rotation: f32,
+}
+
+impl PortalTransform {
+    pub fn between(from: PortalFace, to: PortalFace) -> Self {
+        // Entering travels against from's normal, leaving travels along to's
+        Self {
+            from_center: from.center(),
+            to_center: to.center(),
+            rotation: to.face.angle() - (from.face.angle() + PI),
+        }
+    }
+
+    pub fn point(&self, point: Vec2f) -> Vec2f {
+        self.to_center + (point - self.from_center).rotate(self.rotation)
+    }
+
+    pub fn direction(&self, direction: Vec2f) -> Vec2f {
+        direction.rotate(self.rotation)
+    }
+}
+
+// Where a body moving by offset ends up after the first portal it crosses
+pub struct PortalTransit {
+    pub position: Vec2f,
+    pub rotation: f32,
+}
+
+// Follows a move the same way a ray would, so what you see through a
+// portal is where walking into it takes you
+pub fn step_through_portal(map: &GameMap, from: Vec2f, offset: Vec2f) -> Option<PortalTransit> {
+    if map.portals.is_empty() || offset.length() < 0.00001 {
+        return None;
+    }
+
+    let mut transit = None;
+    let mut blocked = false;
+    cast_ray_with(&from, offset, map, 1.0, |step| {
+        let (enter, exit, rotation) = match step {
+            RayStep::Portal { enter, exit, rotation } => (enter, exit, rotation),
+            RayStep::Reflect { .. } => {
+                blocked = true; // Mirrors are solid to walk into
+                return;
+            }
+            RayStep::Cell(..) => return,
+        };
+        if blocked || transit.is_some() {
+            return; // Only the first portal counts; the ray may pass several
+        }
+
+        // Whatever is left of the move continues on the far side
+        let travelled = map.shortest_offset(from, enter).length() / offset.length();
+        let remaining = offset.rotate(rotation) * (1.0 - travelled).max(0.0);
+        let landing = exit + remaining;
+        let position = if map.is_valid_position(landing) && !map.segment_blocks(exit, landing) { landing } else { exit };
+        transit = Some(PortalTransit { position, rotation });
+    });
+    transit
+}
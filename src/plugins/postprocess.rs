@@ -0,0 +1,510 @@
+use bevy::prelude::*;
+use std::any::Any;
+use std::path::Path;
+use super::actions::{Action, ActionState};
+use super::canvas::{CanvasPass, PixelCanvas};
+use super::debug::DebugOverlayAppExt;
+use super::hud::HudMessages;
+
+pub const COLOR_LUT_PATH: &str = "assets/lut/default.cube";
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_post_effect(ColorGrade::load(Path::new(COLOR_LUT_PATH)), false)
+            .add_post_effect(GammaContrast::default(), false)
+            .add_post_effect(Pixelate::default(), false)
+            .add_post_effect(OrderedDither::default(), false)
+            .add_post_effect(Vignette::default(), false)
+            .add_post_effect(Scanlines::default(), false)
+            .add_debug_page("Post", post_process_debug_page)
+            .add_systems(Update, (
+                handle_post_process_controls,
+                apply_post_processing.in_set(CanvasPass::PostProcess),
+            ));
+    }
+}
+
+// A filter over the finished frame. Effects run in the order they were
+// added, each seeing the previous one's output.
+pub trait PostEffect: Any + Send + Sync {
+    fn name(&self) -> &'static str;
+    fn apply(&mut self, canvas: &mut PixelCanvas);
+
+    // Current parameters, for the debug page and HUD messages
+    fn settings(&self) -> String {
+        String::new()
+    }
+
+    // Nudges the main parameter up or down a notch
+    fn adjust(&mut self, _steps: i32) {}
+}
+
+pub struct PostProcessEntry {
+    pub enabled: bool,
+    pub effect: Box<dyn PostEffect>,
+}
+
+#[derive(Resource, Default)]
+pub struct PostProcessChain {
+    pub effects: Vec<PostProcessEntry>,
+    pub selected: usize, // Which effect the runtime controls act on
+}
+
+impl PostProcessChain {
+    pub fn add(&mut self, effect: impl PostEffect, enabled: bool) {
+        self.effects.push(PostProcessEntry {
+            enabled,
+            effect: Box::new(effect),
+        });
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.effects.iter_mut().find(|entry| entry.effect.name() == name) {
+            Some(entry) => {
+                entry.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The first effect of a type, to change its parameters
+    pub fn effect_mut<T: PostEffect>(&mut self) -> Option<&mut T> {
+        self.effects.iter_mut().find_map(|entry| (entry.effect.as_mut() as &mut dyn Any).downcast_mut::<T>())
+    }
+}
+
+pub trait PostProcessAppExt {
+    fn add_post_effect(&mut self, effect: impl PostEffect, enabled: bool) -> &mut Self;
+}
+
+impl PostProcessAppExt for App {
+    fn add_post_effect(&mut self, effect: impl PostEffect, enabled: bool) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<PostProcessChain>()
+            .add(effect, enabled);
+        self
+    }
+}
+
+// Runs a function over every pixel's RGB, leaving alpha alone
+fn map_pixels(canvas: &mut PixelCanvas, mut map: impl FnMut(u32, u32, [u8; 3]) -> [u8; 3]) {
+    let width = canvas.width;
+    for (index, pixel) in canvas.pixels.chunks_mut(4).enumerate() {
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        let [r, g, b] = map(x, y, [pixel[0], pixel[1], pixel[2]]);
+        pixel[..3].copy_from_slice(&[r, g, b]);
+    }
+}
+
+// Gamma and contrast folded into one 256 entry table
+pub struct GammaContrast {
+    pub gamma: f32,
+    pub contrast: f32,
+    table: [u8; 256],
+    table_for: (f32, f32),
+}
+
+impl Default for GammaContrast {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            contrast: 1.0,
+            table: std::array::from_fn(|index| index as u8),
+            table_for: (1.0, 1.0),
+        }
+    }
+}
+
+impl PostEffect for GammaContrast {
+    fn name(&self) -> &'static str {
+        "gamma"
+    }
+
+    fn apply(&mut self, canvas: &mut PixelCanvas) {
+        if self.table_for != (self.gamma, self.contrast) {
+            self.table = std::array::from_fn(|index| {
+                let value = (index as f32 / 255.0).powf(1.0 / self.gamma);
+                (((value - 0.5) * self.contrast + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8
+            });
+            self.table_for = (self.gamma, self.contrast);
+        }
+        let table = &self.table;
+        map_pixels(canvas, |_, _, color| color.map(|channel| table[channel as usize]));
+    }
+
+    fn settings(&self) -> String {
+        format!("gamma {:.2}, contrast {:.2}", self.gamma, self.contrast)
+    }
+
+    fn adjust(&mut self, steps: i32) {
+        self.gamma = (self.gamma + steps as f32 * 0.1).clamp(0.2, 3.0);
+    }
+}
+
+// Far larger than grading tools export; 256^3 already covers every 8-bit color
+const MAX_LUT_SIZE: usize = 256;
+
+// A 3D lookup table in the .cube format most grading tools export
+pub struct ColorLut {
+    size: usize,
+    table: Vec<[u8; 3]>, // Red varies fastest, then green, then blue
+}
+
+impl ColorLut {
+    pub fn parse(text: &str) -> Option<ColorLut> {
+        let mut size = None;
+        let mut table = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("LUT_3D_SIZE") {
+                let value = value.trim().parse::<usize>().ok()?;
+                if !(2..=MAX_LUT_SIZE).contains(&value) {
+                    return None;
+                }
+                size = Some(value);
+                continue;
+            }
+            if line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                continue; // TITLE, DOMAIN_MIN and the like
+            }
+
+            let values: Vec<f32> = line.split_whitespace().map(str::parse).collect::<Result<_, _>>().ok()?;
+            let [r, g, b] = values.as_slice() else { return None };
+            table.push([r, g, b].map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+
+        let size = size.filter(|&size| table.len() == size * size * size)?;
+        Some(ColorLut { size, table })
+    }
+
+    pub fn lookup(&self, color: [u8; 3]) -> [u8; 3] {
+        let [r, g, b] = color.map(|channel| (channel as usize * (self.size - 1) + 127) / 255);
+        self.table[(b * self.size + g) * self.size + r]
+    }
+}
+
+pub struct ColorGrade {
+    pub lut: Option<ColorLut>,
+}
+
+impl ColorGrade {
+    // A missing or unreadable file leaves the effect doing nothing
+    pub fn load(path: &Path) -> Self {
+        let lut = std::fs::read_to_string(path).ok().and_then(|text| {
+            let lut = ColorLut::parse(&text);
+            match &lut {
+                Some(lut) => info!("Color LUT loaded from {} ({}^3)", path.display(), lut.size),
+                None => warn!("Could not parse color LUT {}", path.display()),
+            }
+            lut
+        });
+        Self { lut }
+    }
+}
+
+impl PostEffect for ColorGrade {
+    fn name(&self) -> &'static str {
+        "color grade"
+    }
+
+    fn apply(&mut self, canvas: &mut PixelCanvas) {
+        if let Some(lut) = &self.lut {
+            map_pixels(canvas, |_, _, color| lut.lookup(color));
+        }
+    }
+
+    fn settings(&self) -> String {
+        match &self.lut {
+            Some(lut) => format!("{}^3 LUT", lut.size),
+            None => "no LUT loaded".to_string(),
+        }
+    }
+}
+
+// Bayer matrix dithering down to a few levels per channel
+pub struct OrderedDither {
+    pub levels: u32,
+}
+
+impl Default for OrderedDither {
+    fn default() -> Self {
+        Self { levels: 6 }
+    }
+}
+
+impl PostEffect for OrderedDither {
+    fn name(&self) -> &'static str {
+        "dither"
+    }
+
+    fn apply(&mut self, canvas: &mut PixelCanvas) {
+        let step = 255.0 / (self.levels.max(2) - 1) as f32;
+        map_pixels(canvas, |x, y, color| {
+            let threshold = (BAYER_4X4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0 - 0.5;
+            color.map(|channel| {
+                let value = channel as f32 + threshold * step;
+                ((value / step).round() * step).clamp(0.0, 255.0) as u8
+            })
+        });
+    }
+
+    fn settings(&self) -> String {
+        format!("{} levels", self.levels)
+    }
+
+    fn adjust(&mut self, steps: i32) {
+        self.levels = self.levels.saturating_add_signed(steps).clamp(2, 64);
+    }
+}
+
+// Darkens every other row like an old CRT
+pub struct Scanlines {
+    pub darkness: f32,
+}
+
+impl Default for Scanlines {
+    fn default() -> Self {
+        Self { darkness: 0.35 }
+    }
+}
+
+impl PostEffect for Scanlines {
+    fn name(&self) -> &'static str {
+        "scanlines"
+    }
+
+    fn apply(&mut self, canvas: &mut PixelCanvas) {
+        let keep = ((1.0 - self.darkness).clamp(0.0, 1.0) * 256.0) as u32;
+        let row_bytes = canvas.width as usize * 4;
+        for row in canvas.pixels.chunks_mut(row_bytes).skip(1).step_by(2) {
+            for pixel in row.chunks_mut(4) {
+                for channel in &mut pixel[..3] {
+                    *channel = ((*channel as u32 * keep) >> 8) as u8;
+                }
+            }
+        }
+    }
+
+    fn settings(&self) -> String {
+        format!("darkness {:.2}", self.darkness)
+    }
+
+    fn adjust(&mut self, steps: i32) {
+        self.darkness = (self.darkness + steps as f32 * 0.05).clamp(0.0, 1.0);
+    }
+}
+
+// Darkens towards the corners. The per-pixel factors only change with the
+// parameters or canvas size, so they are kept between frames.
+pub struct Vignette {
+    pub strength: f32,
+    pub radius: f32, // Where darkening starts, 0 = center, 1 = corners
+    factors: Vec<u16>, // 256 = unchanged
+    factors_for: (u32, u32, f32, f32),
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            strength: 0.6,
+            radius: 0.5,
+            factors: Vec::new(),
+            factors_for: (0, 0, 0.0, 0.0),
+        }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn name(&self) -> &'static str {
+        "vignette"
+    }
+
+    fn apply(&mut self, canvas: &mut PixelCanvas) {
+        let key = (canvas.width, canvas.height, self.strength, self.radius);
+        if self.factors_for != key {
+            let (width, height) = (canvas.width as f32, canvas.height as f32);
+            self.factors = (0..canvas.width * canvas.height)
+                .map(|index| {
+                    let x = (index % canvas.width) as f32 / width * 2.0 - 1.0;
+                    let y = (index / canvas.width) as f32 / height * 2.0 - 1.0;
+                    let distance = (x * x + y * y).sqrt() / std::f32::consts::SQRT_2;
+                    let fade = ((distance - self.radius) / (1.0 - self.radius).max(0.001)).clamp(0.0, 1.0);
+                    ((1.0 - self.strength * fade * fade) * 256.0) as u16
+                })
+                .collect();
+            self.factors_for = key;
+        }
+
+        for (pixel, &factor) in canvas.pixels.chunks_mut(4).zip(&self.factors) {
+            for channel in &mut pixel[..3] {
+                *channel = ((*channel as u32 * factor as u32) >> 8) as u8;
+            }
+        }
+    }
+
+    fn settings(&self) -> String {
+        format!("strength {:.2}, radius {:.2}", self.strength, self.radius)
+    }
+
+    fn adjust(&mut self, steps: i32) {
+        self.strength = (self.strength + steps as f32 * 0.05).clamp(0.0, 1.0);
+    }
+}
+
+// Blocky low resolution look: each block takes its top-left pixel
+pub struct Pixelate {
+    pub block: u32,
+}
+
+impl Default for Pixelate {
+    fn default() -> Self {
+        Self { block: 2 }
+    }
+}
+
+impl PostEffect for Pixelate {
+    fn name(&self) -> &'static str {
+        "pixelate"
+    }
+
+    fn apply(&mut self, canvas: &mut PixelCanvas) {
+        if self.block <= 1 {
+            return;
+        }
+        for y in 0..canvas.height {
+            let source_y = y - y % self.block;
+            for x in 0..canvas.width {
+                let source_x = x - x % self.block;
+                if (source_x, source_y) != (x, y) {
+                    let color = canvas.get_pixel(source_x, source_y);
+                    let index = ((y * canvas.width + x) * 4) as usize;
+                    canvas.pixels[index..index + 4].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    fn settings(&self) -> String {
+        format!("{}px blocks", self.block)
+    }
+
+    fn adjust(&mut self, steps: i32) {
+        self.block = self.block.saturating_add_signed(steps).clamp(1, 16);
+    }
+}
+
+fn apply_post_processing(
+    mut chain: ResMut<PostProcessChain>,
+    mut canvas: ResMut<PixelCanvas>,
+) {
+    // An unchanged canvas was already filtered; running again would stack
+    if !canvas.is_changed() || chain.effects.iter().all(|entry| !entry.enabled) {
+        return;
+    }
+    for entry in chain.effects.iter_mut().filter(|entry| entry.enabled) {
+        entry.effect.apply(&mut canvas);
+    }
+}
+
+fn handle_post_process_controls(
+    actions: Res<ActionState>,
+    mut chain: ResMut<PostProcessChain>,
+    mut messages: ResMut<HudMessages>,
+) {
+    if chain.effects.is_empty() {
+        return;
+    }
+
+    let chain = &mut *chain;
+    if actions.just_pressed(Action::NextPostEffect) {
+        chain.selected = (chain.selected + 1) % chain.effects.len();
+    }
+    let Some(entry) = chain.effects.get_mut(chain.selected) else { return };
+    if actions.just_pressed(Action::TogglePostEffect) {
+        entry.enabled = !entry.enabled;
+    }
+    if actions.just_pressed(Action::PostEffectLess) {
+        entry.effect.adjust(-1);
+    }
+    if actions.just_pressed(Action::PostEffectMore) {
+        entry.effect.adjust(1);
+    }
+
+    let touched = [Action::NextPostEffect, Action::TogglePostEffect, Action::PostEffectLess, Action::PostEffectMore]
+        .into_iter()
+        .any(|action| actions.just_pressed(action));
+    if touched {
+        messages.push(format!(
+            "{}: {} ({})",
+            entry.effect.name(),
+            if entry.enabled { "on" } else { "off" },
+            entry.effect.settings(),
+        ));
+    }
+}
+
+fn post_process_debug_page(world: &World) -> Vec<String> {
+    let chain = world.resource::<PostProcessChain>();
+    chain.effects.iter()
+        .enumerate()
+        .map(|(index, entry)| format!(
+            "{}[{}] {} {}",
+            if index == chain.selected { ">" } else { " " },
+            if entry.enabled { "x" } else { " " },
+            entry.effect.name(),
+            entry.effect.settings(),
+        ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity_cube() -> String {
+        let mut text = String::from("TITLE \"identity\"\n# Red varies fastest\nLUT_3D_SIZE 2\n");
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    text.push_str(&format!("{} {} {}\n", r, g, b));
+                }
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn identity_lut() {
+        let lut = ColorLut::parse(&identity_cube()).unwrap();
+        assert_eq!(lut.size, 2);
+        for color in [[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 255, 0], [0, 0, 255], [200, 10, 255]] {
+            let snapped = color.map(|channel: u8| if channel >= 128 { 255 } else { 0 });
+            assert_eq!(lut.lookup(color), snapped);
+        }
+    }
+
+    #[test]
+    fn malformed_luts() {
+        let cube = identity_cube();
+        assert!(ColorLut::parse(&cube.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 3")).is_none());
+        assert!(ColorLut::parse(&cube.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 1")).is_none());
+        assert!(ColorLut::parse(&cube.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 99999999999")).is_none());
+        assert!(ColorLut::parse(&cube.replace("LUT_3D_SIZE 2", &format!("LUT_3D_SIZE {}", usize::MAX))).is_none());
+        assert!(ColorLut::parse(&cube.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE two")).is_none());
+        assert!(ColorLut::parse(&cube.replace("1 1 1", "1 1")).is_none());
+        assert!(ColorLut::parse(&cube.replace("1 1 1", "1 1 x")).is_none());
+        assert!(ColorLut::parse(&cube.replace("LUT_3D_SIZE 2\n", "")).is_none());
+    }
+}
@@ -1,80 +1,543 @@
 use bevy::prelude::*;
-use super::canvas::{PixelCanvas, CANVAS_WIDTH, CANVAS_HEIGHT};
-use super::player::Player;
-use super::map::GameMap;
+use bevy::ecs::system::SystemParam;
+use std::time::Instant;
+use super::canvas::{PixelCanvas, CanvasPass};
+use super::player::{PlayerView, PlayerSystems};
+use super::map::{FloorMaterial, GameMap, Mirror, OutOfBounds};
+use super::lighting::LightMap;
+use super::sky::Sky;
+use super::palette::{Palette, PaletteMode};
+use super::surface::{AnimationClock, SurfaceTextures};
 use super::math::Vec2f;
+use super::debug::DebugOverlayAppExt;
+use super::portal::{Face, PortalFace, PortalTransform, MAX_PORTAL_DEPTH, PORTAL_EXIT_NUDGE};
+
+pub const DEFAULT_MAX_RAY_DISTANCE: f32 = 128.0;
+// Longest distance a ray may be asked to travel. On a wrapping or open map
+// nothing else stops it, so larger, infinite or NaN limits are clamped.
+pub const MAX_RAY_DISTANCE: f32 = 4096.0;
+// Backstop for a single DDA walk, in case a limit still slips through
+const MAX_DDA_STEPS: u32 = 1 << 16;
+pub const MAX_MIRROR_BOUNCES: u32 = 4;
+// The viewer's body as seen in mirrors: a cylinder this wide and this tall
+// relative to a wall
+pub const AVATAR_RADIUS: f32 = 0.2;
+const AVATAR_HEIGHT: f32 = 0.75;
+const AVATAR_COLOR: [u8; 4] = [230, 180, 140, 255];
+const SKY_COLOR: [u8; 4] = [135, 206, 235, 255]; // Sky blue, when there is no panorama
+const SKY_SPAN: f32 = 0.75; // Screen heights the panorama covers above the horizon
 
 pub struct RaycastPlugin;
 
 impl Plugin for RaycastPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, render_3d_view);
+        app
+            .insert_resource(RaycastSettings::default())
+            .insert_resource(RenderStats::default())
+            .add_debug_page("Render", render_debug_page)
+            .add_systems(Update, render_3d_view.in_set(CanvasPass::World).after(PlayerSystems::Interpolate));
+    }
+}
+
+pub struct RayHit {
+    pub distance: f32, // Perpendicular distance, also the ray parameter t, summed over portals
+    pub wall_type: u8,
+    pub side: bool, // false = NS wall, true = EW wall
+    pub face: Face, // Face of the wall cell the ray struck
+    pub normal: Vec2f, // Unit normal of the wall, facing the ray
+    pub map_x: i32,
+    pub map_y: i32,
+    pub point: Vec2f, // Where on the wall, in map coordinates
+    pub texture_u: f32, // Along the wall from its left edge, in cells; runs the length of thin walls
+    pub thin: bool, // Hit a thin wall inside a cell rather than a whole-cell block
+    pub portals: u32, // Portals passed through on the way
+    pub bounces: u32, // Mirrors reflected off on the way
+    pub tint: Tint,   // What those mirrors do to the wall's color
+    pub avatar: Option<AvatarHit>, // The viewer seen in a mirror or through a portal
+}
+
+// Color seen through a chain of mirrors: base + weight * what lies beyond
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tint {
+    pub base: [f32; 3],
+    pub weight: f32,
+}
+
+impl Default for Tint {
+    fn default() -> Self {
+        Self {
+            base: [0.0; 3],
+            weight: 1.0,
+        }
+    }
+}
+
+impl Tint {
+    // Looking at a mirror shows its own color blended with the reflection
+    pub fn through(self, mirror: &Mirror) -> Tint {
+        let own = self.weight * (1.0 - mirror.reflectivity);
+        Tint {
+            base: std::array::from_fn(|channel| self.base[channel] + own * mirror.color[channel] as f32),
+            weight: self.weight * mirror.reflectivity,
+        }
+    }
+    
+    pub fn apply(&self, color: [u8; 4]) -> [u8; 4] {
+        if *self == Tint::default() {
+            return color;
+        }
+        let channel = |index: usize| (self.base[index] + self.weight * color[index] as f32).clamp(0.0, 255.0) as u8;
+        [channel(0), channel(1), channel(2), color[3]]
     }
 }
 
-struct RayHit {
-    distance: f32,
-    wall_type: u8,
-    side: bool, // false = NS wall, true = EW wall
+#[derive(Debug, Clone, Copy)]
+pub struct AvatarHit {
+    pub distance: f32,
+    pub tint: Tint,
+}
+
+// What a ray's traversal reports as it goes, for debugging and for
+// following a move through portals
+#[derive(Debug, Clone, Copy)]
+pub enum RayStep {
+    Cell(i32, i32), // Unwrapped, so on a wrapping map it can lie outside it
+    Portal { enter: Vec2f, exit: Vec2f, rotation: f32 },
+    Reflect { point: Vec2f },
+}
+
+#[derive(Resource)]
+pub struct RaycastSettings {
+    pub max_distance: f32, // Walls further away than this are not drawn
+}
+
+impl Default for RaycastSettings {
+    fn default() -> Self {
+        Self {
+            max_distance: DEFAULT_MAX_RAY_DISTANCE,
+        }
+    }
 }
 
-fn render_3d_view(
+// Per-frame numbers for the debug overlay
+#[derive(Resource, Default)]
+pub struct RenderStats {
+    pub pass_times: Vec<(&'static str, f32)>, // Milliseconds, in draw order
+    pub rays_cast: u32,
+    pub crosshair_tile: Option<(i32, i32, u8)>,
+}
+
+impl RenderStats {
+    pub fn record_pass(&mut self, name: &'static str, started: Instant) {
+        let ms = started.elapsed().as_secs_f32() * 1000.0;
+        match self.pass_times.iter_mut().find(|(pass, _)| *pass == name) {
+            Some(entry) => entry.1 = ms,
+            None => self.pass_times.push((name, ms)),
+        }
+    }
+}
+
+fn render_debug_page(world: &World) -> Vec<String> {
+    let stats = world.resource::<RenderStats>();
+    let mut lines = vec![format!("Rays cast: {}", stats.rays_cast)];
+    
+    for (pass, ms) in &stats.pass_times {
+        lines.push(format!("{:<8} {:.3} ms", pass, ms));
+    }
+    
+    lines.push(match stats.crosshair_tile {
+        Some((x, y, tile)) => format!("Crosshair: tile {} at ({}, {})", tile, x, y),
+        None => "Crosshair: no hit".to_string(),
+    });
+    lines
+}
+
+pub fn render_3d_view(
     mut canvas: ResMut<PixelCanvas>,
-    player: Res<Player>,
+    player: Res<PlayerView>,
     map: Res<GameMap>,
+    settings: Res<RaycastSettings>,
+    extras: FrameExtras,
+    mut stats: ResMut<RenderStats>,
+) {
+    render_frame(&mut canvas, &player, &map, &settings, extras.style(), &mut stats);
+}
+
+// Resources other plugins provide that change how a frame is drawn; any of
+// them may be missing
+#[derive(SystemParam)]
+pub struct FrameExtras<'w> {
+    light_map: Option<Res<'w, LightMap>>,
+    sky: Option<Res<'w, Sky>>,
+    palette_mode: Option<Res<'w, PaletteMode>>,
+    textures: Option<Res<'w, SurfaceTextures>>,
+    clock: Option<Res<'w, AnimationClock>>,
+}
+
+impl FrameExtras<'_> {
+    pub fn style(&self) -> FrameStyle<'_> {
+        FrameStyle {
+            lighting: self.light_map.as_deref().filter(|light_map| light_map.is_active()),
+            sky: self.sky.as_deref(),
+            palette: self.palette_mode.as_deref().filter(|mode| mode.enabled).map(|mode| &mode.palette),
+            textures: self.textures.as_deref(),
+            time: self.clock.as_ref().map_or(0.0, |clock| clock.seconds),
+        }
+    }
+}
+
+// Optional extras a frame is drawn with; the default is flat colors on a
+// plain sky, straight to RGBA
+#[derive(Default, Clone, Copy)]
+pub struct FrameStyle<'a> {
+    pub lighting: Option<&'a LightMap>,
+    pub sky: Option<&'a Sky>,
+    pub palette: Option<&'a Palette>, // Draw palette indices instead of RGBA
+    pub textures: Option<&'a SurfaceTextures>,
+    pub time: f32, // Animation clock, in seconds
+}
+
+// A finished surface color, ready for the canvas
+#[derive(Clone, Copy)]
+enum Shade {
+    Color([u8; 4]),
+    Index(u8),
+}
+
+impl FrameStyle<'_> {
+    // A surface's color lit at point, seen from distance away through tint.
+    // In palette mode the light becomes a colormap level, so the lit color
+    // is a table lookup.
+    fn surface(&self, color: [u8; 4], point: Vec2f, distance: f32, tint: Tint) -> Shade {
+        match self.palette {
+            Some(palette) => {
+                let light = self.lighting.map_or(1.0, |lighting| lighting.brightness(point));
+                let index = palette.index_of(tint.apply(color));
+                Shade::Index(palette.shade(index, palette.light_level(distance, light)))
+            }
+            None => {
+                let lit = self.lighting.map_or(color, |lighting| lighting.shade(color, point));
+                Shade::Color(tint.apply(lit))
+            }
+        }
+    }
+    
+    // Full bright, e.g. the sky
+    fn unlit(&self, color: [u8; 4]) -> Shade {
+        match self.palette {
+            Some(palette) => Shade::Index(palette.index_of(color)),
+            None => Shade::Color(color),
+        }
+    }
+}
+
+fn plot(canvas: &mut PixelCanvas, x: u32, y: u32, shade: Shade) {
+    match shade {
+        Shade::Color(color) => canvas.set_pixel(x, y, color),
+        Shade::Index(index) => canvas.set_index(x, y, index),
+    }
+}
+
+// One full 3D frame at the canvas' own resolution; split out of the system
+// so benchmarks can drive it without an App
+pub fn render_frame(
+    canvas: &mut PixelCanvas,
+    player: &PlayerView,
+    map: &GameMap,
+    settings: &RaycastSettings,
+    style: FrameStyle,
+    stats: &mut RenderStats,
 ) {
-    canvas.clear([135, 206, 235, 255]); // Sky blue
+    // Palette mode needs somewhere to put the indices
+    let style = FrameStyle {
+        palette: style.palette.filter(|_| canvas.indices.is_some()),
+        ..style
+    };
+    
+    if style.sky.is_none() {
+        let started = Instant::now();
+        match style.unlit(SKY_COLOR) {
+            Shade::Color(color) => canvas.clear(color),
+            Shade::Index(index) => canvas.clear_indices(index),
+        }
+        stats.record_pass("clear", started);
+    }
+    
+    let screen_width = canvas.width;
+    let screen_height = canvas.height as f32;
     
-    let screen_width = CANVAS_WIDTH as f32;
-    let screen_height = CANVAS_HEIGHT as f32;
+    let projection = projection_distance(screen_height);
+    let horizon = horizon_row(player.pitch, screen_height);
     
-    // Calculate vertical offset from pitch - clamp to prevent overflow
-    let pitch_offset = (player.pitch * screen_height * 0.3).clamp(-200.0, 200.0) as i32;
-    let horizon = ((screen_height / 2.0) as i32 + pitch_offset).clamp(0, screen_height as i32 - 1);
+    // A point at height z, d away, lands (eye - z) * h / d below the
+    // horizon. Walls run from z = 0 to 1, so unless the eye is halfway up
+    // they sit off-center. Kept inside the wall so floor and ceiling stay
+    // on their own sides of the horizon.
+    let eye = player.eye_height.clamp(0.05, 0.95);
     
     // Cast rays for each vertical line on screen
-    for x in 0..CANVAS_WIDTH {
-        let camera_x = 2.0 * x as f32 / screen_width - 1.0;
-        let ray_dir = Vec2f::new(
-            player.direction.x + player.plane.x * camera_x,
-            player.direction.y + player.plane.y * camera_x,
-        );
-        
-        if let Some(hit) = cast_ray(&player.position, ray_dir, &map) {
-            let line_height = ((screen_height / hit.distance.max(0.01)) as i32).min(screen_height as i32 * 2);
-            let wall_half = line_height / 2;
-            
-            let draw_start = (horizon - wall_half).max(0).min(screen_height as i32 - 1) as u32;
-            let draw_end = (horizon + wall_half).max(0).min(screen_height as i32 - 1) as u32;
-            
-            let wall_color = get_wall_color(hit.wall_type, hit.side);
-            
-            // Draw floor (only if there's space below the wall)
-            if draw_end < CANVAS_HEIGHT - 1 {
-                for y in (draw_end + 1)..CANVAS_HEIGHT {
-                    canvas.set_pixel(x, y, [34, 139, 34, 255]); // Forest green floor
-                }
-            }
+    let started = Instant::now();
+    let hits: Vec<Option<RayHit>> = (0..screen_width)
+        .map(|x| cast_ray(&player.position, column_ray_dir(player, x, screen_width), map, settings.max_distance))
+        .collect();
+    stats.record_pass("cast", started);
+    stats.rays_cast = screen_width;
+    stats.crosshair_tile = hits[screen_width as usize / 2]
+        .as_ref()
+        .map(|hit| (hit.map_x, hit.map_y, hit.wall_type));
+    
+    // Screen rows covered by each column's wall
+    let spans: Vec<Option<(u32, u32)>> = hits.iter()
+        .map(|hit| hit.as_ref().map(|hit| {
+            let line_height = (projection / hit.distance.max(0.01)).min(screen_height * 2.0);
+            let above = (line_height * (1.0 - eye)) as i32;
+            let below = (line_height * eye) as i32;
             
-            // Draw wall
-            if draw_start <= draw_end {
-                for y in draw_start..=draw_end {
-                    if y < CANVAS_HEIGHT {
-                        canvas.set_pixel(x, y, wall_color);
+            let draw_start = (horizon - above).max(0).min(screen_height as i32 - 1) as u32;
+            let draw_end = (horizon + below).max(0).min(screen_height as i32 - 1) as u32;
+            (draw_start, draw_end)
+        }))
+        .collect();
+    
+    // Ceilings and sky above the wall, or down to the horizon when nothing was hit
+    if let Some(sky) = style.sky {
+        let started = Instant::now();
+        for (x, span) in spans.iter().enumerate() {
+            let sky_end = match span {
+                Some((draw_start, _)) => *draw_start,
+                None => horizon as u32 + 1,
+            };
+            let ray_dir = column_ray_dir(player, x as u32, screen_width);
+            let angle = ray_dir.y.atan2(ray_dir.x);
+            for y in 0..sky_end {
+                // Mirrors the floor: a row p above the horizon sees the
+                // ceiling, 1 - eye overhead, at (1 - eye) * h / p
+                let above = horizon - y as i32;
+                let ceiling = (above > 0).then(|| {
+                    let row_distance = (1.0 - eye) * projection / above as f32;
+                    let point = player.position + ray_dir * row_distance;
+                    (point, row_distance, map.ceiling_at(point.x.floor() as i32, point.y.floor() as i32))
+                });
+                let shade = match ceiling {
+                    Some((point, distance, ceiling)) if ceiling != 0 => {
+                        style.surface(get_ceiling_color(ceiling), point, distance, Tint::default())
                     }
-                }
+                    _ => style.unlit(sky.sample(angle, above as f32 / (screen_height * SKY_SPAN))),
+                };
+                plot(canvas, x as u32, y, shade);
+            }
+        }
+        stats.record_pass("sky", started);
+    }
+    
+    // Draw floor below the wall, or below the horizon when nothing was hit
+    let started = Instant::now();
+    for (x, span) in spans.iter().enumerate() {
+        let floor_start = match span {
+            Some((_, draw_end)) => draw_end + 1,
+            None => horizon as u32 + 1,
+        };
+        if style.lighting.is_none() && style.palette.is_none() && map.floors.is_empty() {
+            for y in floor_start..canvas.height {
+                canvas.set_pixel(x as u32, y, floor_color(&FloorMaterial::GROUND));
             }
+            continue;
+        }
+        
+        // Shaded or mixed floors need to know where each row lands: a wall
+        // at distance d has its foot at horizon + eye * h / d, so a row p
+        // below the horizon sees the floor at eye * h / p
+        let ray_dir = column_ray_dir(player, x as u32, screen_width);
+        for y in floor_start.max(horizon as u32 + 1)..canvas.height {
+            let row_distance = eye * projection / (y as i32 - horizon) as f32;
+            let floor_point = player.position + ray_dir * row_distance;
+            let color = floor_color(&map.floor_material(floor_point));
+            plot(canvas, x as u32, y, style.surface(color, floor_point, row_distance, Tint::default()));
+        }
+    }
+    stats.record_pass("floor", started);
+    
+    // Draw walls
+    let started = Instant::now();
+    for (x, (hit, span)) in hits.iter().zip(&spans).enumerate() {
+        let (Some(hit), Some((draw_start, draw_end))) = (hit, span) else { continue };
+        // Sample just in front of the wall, where the light falls
+        let light_point = hit.point + hit.normal * 0.5;
+        
+        let surface = map.surfaces.get(&hit.wall_type);
+        let Some((surface, frame)) = surface.zip(style.textures).and_then(|(surface, textures)| {
+            textures.frame(surface, style.time).map(|frame| (surface, frame))
+        }) else {
+            let shade = style.surface(get_wall_color(hit.wall_type, hit.side), light_point, hit.distance, hit.tint);
+            for y in *draw_start..=*draw_end {
+                plot(canvas, x as u32, y, shade);
+            }
+            continue;
+        };
+        
+        // v runs down the whole wall, including any part clipped off screen
+        let line_height = (projection / hit.distance.max(0.01)).min(screen_height * 2.0);
+        let wall_top = horizon as f32 - line_height * (1.0 - eye);
+        for y in *draw_start..=*draw_end {
+            let v = (y as f32 + 0.5 - wall_top) / line_height;
+            let (u, v) = surface.scrolled(hit.texture_u, v, style.time);
+            let texel = shade_side(SurfaceTextures::sample(frame, u, v), hit.side);
+            plot(canvas, x as u32, y, style.surface(texel, light_point, hit.distance, hit.tint));
+        }
+    }
+    stats.record_pass("walls", started);
+    
+    // The viewer's reflection stands on the floor in front of whatever wall is behind it
+    let started = Instant::now();
+    for (x, hit) in hits.iter().enumerate() {
+        let Some(avatar) = hit.as_ref().and_then(|hit| hit.avatar) else { continue };
+        let line_height = (projection / avatar.distance.max(0.01)).min(screen_height * 2.0);
+        let floor = horizon as f32 + line_height * eye;
+        let top = (floor - line_height * AVATAR_HEIGHT).max(0.0) as u32;
+        let bottom = floor.min(screen_height - 1.0).max(0.0) as u32;
+        let shade = style.surface(AVATAR_COLOR, player.position, avatar.distance, avatar.tint);
+        for y in top..=bottom {
+            plot(canvas, x as u32, y, shade);
+        }
+    }
+    stats.record_pass("avatar", started);
+}
+
+// Pixels from the eye to the screen, vertically: something one unit tall
+// one unit away is this many rows high. Equal to the screen height, which
+// makes the vertical field of view 2 * atan(0.5), about 53 degrees.
+pub fn projection_distance(screen_height: f32) -> f32 {
+    screen_height
+}
+
+// Screen row of the horizon for a pitch in radians. The view is sheared
+// rather than rotated, so the horizon moves by tan(pitch) projection
+// distances and everything drawn relative to it follows. The clamp only
+// matters past MAX_PITCH, which the player never reaches.
+pub fn horizon_row(pitch: f32, screen_height: f32) -> i32 {
+    let shift = pitch.tan() * projection_distance(screen_height);
+    ((screen_height / 2.0 + shift) as i32).clamp(0, screen_height as i32 - 1)
+}
+
+// Ray direction for screen column x; not normalized, so the distance a
+// ray reports is already the perpendicular (fisheye-free) distance
+pub fn column_ray_dir(view: &PlayerView, x: u32, screen_width: u32) -> Vec2f {
+    let camera_x = 2.0 * x as f32 / screen_width as f32 - 1.0;
+    Vec2f::new(
+        view.direction.x + view.plane.x * camera_x,
+        view.direction.y + view.plane.y * camera_x,
+    )
+}
+
+pub fn cast_ray(start: &Vec2f, direction: Vec2f, map: &GameMap, max_distance: f32) -> Option<RayHit> {
+    cast_ray_with(start, direction, map, max_distance, |_| {})
+}
+
+// Same as cast_ray, reporting every cell the DDA steps into and every
+// portal or mirror on the way. A ray is a chain of straight segments: each
+// portal restarts the traversal from its exit with a rotated direction,
+// each mirror from the hit point with a reflected one, and the distance
+// carries on from where the last segment stopped.
+pub fn cast_ray_with(
+    start: &Vec2f,
+    direction: Vec2f,
+    map: &GameMap,
+    max_distance: f32,
+    mut visit: impl FnMut(RayStep),
+) -> Option<RayHit> {
+    let max_distance = clamp_ray_distance(max_distance);
+    let mut origin = *start;
+    let mut direction = direction;
+    let mut travelled = 0.0;
+    let mut portals = 0;
+    let mut bounces = 0;
+    let mut tint = Tint::default();
+    let mut avatar = None;
+    
+    loop {
+        let hit = trace_segment(&origin, direction, map, max_distance - travelled, &mut visit)?;
+        
+        // The viewer stands at the ray's start, so once the ray has turned
+        // around it can come back and see them
+        if avatar.is_none() && (portals > 0 || bounces > 0) {
+            avatar = avatar_distance(&origin, direction, start, hit.distance)
+                .map(|distance| AvatarHit { distance: travelled + distance, tint });
+        }
+        
+        let entry = PortalFace::new(hit.map_x, hit.map_y, hit.face);
+        if let Some(exit) = map.portal_exit(entry).filter(|_| !hit.thin && portals < MAX_PORTAL_DEPTH) {
+            let transform = PortalTransform::between(entry, exit);
+            let exit_point = transform.point(hit.point) + exit.face.normal() * PORTAL_EXIT_NUDGE;
+            visit(RayStep::Portal { enter: hit.point, exit: exit_point, rotation: transform.rotation });
+            
+            travelled += hit.distance;
+            portals += 1;
+            origin = exit_point;
+            direction = transform.direction(direction);
+            continue;
+        }
+        
+        if let Some(mirror) = map.mirror(hit.wall_type).filter(|_| bounces < MAX_MIRROR_BOUNCES) {
+            visit(RayStep::Reflect { point: hit.point });
+            
+            // Reflect about the wall's normal; this keeps the length, so
+            // distances carry on in the same units
+            direction = direction - hit.normal * (2.0 * direction.dot(&hit.normal));
+            travelled += hit.distance;
+            bounces += 1;
+            tint = tint.through(mirror);
+            origin = hit.point + hit.normal * PORTAL_EXIT_NUDGE;
+            continue;
         }
+        
+        return Some(RayHit {
+            distance: (travelled + hit.distance).max(0.01),
+            portals,
+            bounces,
+            tint,
+            avatar,
+            ..hit
+        });
+    }
+}
+
+fn clamp_ray_distance(max_distance: f32) -> f32 {
+    if max_distance.is_nan() {
+        0.0
+    } else {
+        max_distance.clamp(0.0, MAX_RAY_DISTANCE)
     }
 }
 
-fn cast_ray(start: &Vec2f, direction: Vec2f, map: &GameMap) -> Option<RayHit> {
+// Where a segment first enters the viewer's body, a cylinder around their position
+fn avatar_distance(origin: &Vec2f, direction: Vec2f, viewer: &Vec2f, max_distance: f32) -> Option<f32> {
+    let offset = *origin - *viewer;
+    let a = direction.x * direction.x + direction.y * direction.y;
+    let b = 2.0 * (offset.x * direction.x + offset.y * direction.y);
+    let c = offset.x * offset.x + offset.y * offset.y - AVATAR_RADIUS * AVATAR_RADIUS;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    
+    let distance = (-b - discriminant.sqrt()) / (2.0 * a);
+    (distance > 0.0 && distance < max_distance).then_some(distance)
+}
+
+// One straight DDA walk; distance is measured from this segment's origin
+fn trace_segment(
+    start: &Vec2f,
+    direction: Vec2f,
+    map: &GameMap,
+    max_distance: f32,
+    visit: &mut impl FnMut(RayStep),
+) -> Option<RayHit> {
     if direction.x.abs() < 0.00001 && direction.y.abs() < 0.00001 {
         return None; // Invalid direction
     }
     
-    let mut map_x = start.x as i32;
-    let mut map_y = start.y as i32;
+    let mut map_x = start.x.floor() as i32;
+    let mut map_y = start.y.floor() as i32;
     
     let delta_dist_x = if direction.x.abs() < 0.00001 { 1e30 } else { (1.0 / direction.x).abs() };
     let delta_dist_y = if direction.y.abs() < 0.00001 { 1e30 } else { (1.0 / direction.y).abs() };
@@ -91,62 +554,359 @@ fn cast_ray(start: &Vec2f, direction: Vec2f, map: &GameMap) -> Option<RayHit> {
         (1, (map_y as f32 + 1.0 - start.y) * delta_dist_y)
     };
     
-    // DDA (Digital Differential Analyzer)
-    let mut side = false;
+    // Thin walls in the cell the segment starts in
+    let first_boundary = side_dist_x.min(side_dist_y).min(max_distance);
+    if let Some(hit) = hit_thin_walls(start, direction, map, (map_x, map_y), 0.0, first_boundary) {
+        return Some(hit);
+    }
     
-    // Limit iterations to prevent infinite loops
-    for _ in 0..100 {
-        if side_dist_x < side_dist_y {
+    // DDA (Digital Differential Analyzer). Side distances are in units of
+    // the ray parameter, which is the perpendicular distance, so stopping
+    // once the next boundary lies past max_distance bounds the walk
+    // regardless of map size.
+    for _ in 0..MAX_DDA_STEPS {
+        let distance = side_dist_x.min(side_dist_y);
+        if distance > max_distance {
+            return None;
+        }
+        
+        let side = if side_dist_x < side_dist_y {
             side_dist_x += delta_dist_x;
             map_x += step_x;
-            side = false;
+            false
         } else {
             side_dist_y += delta_dist_y;
             map_y += step_y;
-            side = true;
-        }
+            true
+        };
         
-        // Check bounds
-        if map_x < 0 || map_y < 0 || map_x >= map.width as i32 || map_y >= map.height as i32 {
-            return None;
+        visit(RayStep::Cell(map_x, map_y));
+        
+        // Past the edge of a void map and heading further out: nothing left to hit
+        if map.out_of_bounds == OutOfBounds::Void && !map.in_bounds(map_x, map_y) {
+            let leaving_x = (map_x < 0 && step_x < 0) || (map_x >= map.width as i32 && step_x > 0);
+            let leaving_y = (map_y < 0 && step_y < 0) || (map_y >= map.height as i32 && step_y > 0);
+            if leaving_x || leaving_y {
+                return None;
+            }
         }
         
-        let wall_type = map.get_tile(map_x as usize, map_y as usize);
+        let wall_type = map.tile_at(map_x, map_y);
         if wall_type > 0 {
-            let perp_wall_dist = if !side {
-                (map_x as f32 - start.x + (1 - step_x) as f32 / 2.0) / direction.x
-            } else {
-                (map_y as f32 - start.y + (1 - step_y) as f32 / 2.0) / direction.y
+            // The face we came in through is the one facing back along the ray
+            let face = match (side, step_x > 0, step_y > 0) {
+                (false, true, _) => Face::West,
+                (false, false, _) => Face::East,
+                (true, _, true) => Face::North,
+                (true, _, false) => Face::South,
             };
             
+            // Report the hit in the wrapped cell's frame
+            let (cell_x, cell_y) = map.wrap_cell(map_x, map_y);
+            let shift = Vec2f::new((cell_x - map_x) as f32, (cell_y - map_y) as f32);
+            let point = *start + direction * distance + shift;
             return Some(RayHit {
-                distance: perp_wall_dist.abs().max(0.01),
+                distance,
                 wall_type,
                 side,
+                face,
+                normal: face.normal(),
+                map_x: cell_x,
+                map_y: cell_y,
+                point,
+                texture_u: face_texture_u(face, point),
+                thin: false,
+                portals: 0,
+                bounces: 0,
+                tint: Tint::default(),
+                avatar: None,
             });
         }
+        
+        // Thin walls in the stretch of ray that lies inside this cell
+        let leave = side_dist_x.min(side_dist_y).min(max_distance);
+        if let Some(hit) = hit_thin_walls(start, direction, map, (map_x, map_y), distance, leave) {
+            return Some(hit);
+        }
     }
-    
     None
 }
 
-fn get_wall_color(wall_type: u8, side: bool) -> [u8; 4] {
+// Nearest thin wall in a cell whose crossing lies between enter and leave
+fn hit_thin_walls(
+    start: &Vec2f,
+    direction: Vec2f,
+    map: &GameMap,
+    (map_x, map_y): (i32, i32),
+    enter: f32,
+    leave: f32,
+) -> Option<RayHit> {
+    let (segment, hit) = map.segments_at(map_x, map_y)
+        .iter()
+        .filter_map(|segment| {
+            let hit = segment.in_cell(map_x, map_y).intersect_ray(*start, direction)?;
+            (hit.distance >= enter && hit.distance <= leave).then_some((segment, hit))
+        })
+        .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))?;
+    
+    let (cell_x, cell_y) = map.wrap_cell(map_x, map_y);
+    let shift = Vec2f::new((cell_x - map_x) as f32, (cell_y - map_y) as f32);
+    Some(RayHit {
+        distance: hit.distance,
+        wall_type: segment.wall_type,
+        side: hit.normal.y.abs() > hit.normal.x.abs(), // Shade like the block face it is closest to
+        face: Face::nearest(hit.normal),
+        normal: hit.normal,
+        map_x: cell_x,
+        map_y: cell_y,
+        point: *start + direction * hit.distance + shift,
+        texture_u: hit.along,
+        thin: true,
+        portals: 0,
+        bounces: 0,
+        tint: Tint::default(),
+        avatar: None,
+    })
+}
+
+// Runs left to right as seen from in front of the face
+fn face_texture_u(face: Face, point: Vec2f) -> f32 {
+    let fract = |value: f32| value - value.floor();
+    match face {
+        Face::South => fract(point.x),
+        Face::North => 1.0 - fract(point.x),
+        Face::West => fract(point.y),
+        Face::East => 1.0 - fract(point.y),
+    }
+}
+
+pub fn floor_color(material: &FloorMaterial) -> [u8; 4] {
+    let [r, g, b] = material.color;
+    [r, g, b, 255]
+}
+
+pub fn get_ceiling_color(ceiling_type: u8) -> [u8; 4] {
+    match ceiling_type {
+        1 => [90, 80, 70, 255],    // Wood
+        2 => [120, 120, 130, 255], // Stone
+        _ => [100, 100, 100, 255],
+    }
+}
+
+pub fn get_wall_color(wall_type: u8, side: bool) -> [u8; 4] {
     let base_color = match wall_type {
         1 => [255, 0, 0],     // Red walls
         2 => [0, 255, 0],     // Green walls
         3 => [0, 0, 255],     // Blue walls
         4 => [255, 255, 0],   // Yellow walls
         5 => [255, 0, 255],   // Magenta walls
+        6 => [170, 200, 220], // Mirror frame, seen once bounces run out
+        7 => [40, 120, 140],  // Screen, when its texture isn't loaded
+        8 => [200, 170, 30],  // Conveyor
+        9 => [50, 110, 200],  // Waterfall
+        10 => [220, 50, 40],  // Switch, off
+        11 => [60, 220, 80],  // Switch, on
         _ => [128, 128, 128], // Default gray
     };
     
-    // Make EW walls darker than NS walls for depth perception
+    shade_side([base_color[0], base_color[1], base_color[2], 255], side)
+}
+
+// Make EW walls darker than NS walls for depth perception
+fn shade_side(color: [u8; 4], side: bool) -> [u8; 4] {
     let brightness = if side { 0.7 } else { 1.0 };
     
     [
-        (base_color[0] as f32 * brightness) as u8,
-        (base_color[1] as f32 * brightness) as u8,
-        (base_color[2] as f32 * brightness) as u8,
-        255,
+        (color[0] as f32 * brightness) as u8,
+        (color[1] as f32 * brightness) as u8,
+        (color[2] as f32 * brightness) as u8,
+        color[3],
     ]
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::map::MIRROR_TILE;
+    use crate::plugins::portal::step_through_portal;
+    use crate::plugins::segment::WallSegment;
+
+    const EAST: Vec2f = Vec2f { x: 1.0, y: 0.0 };
+
+    // Empty room walled in with tile 1
+    fn room(width: usize, height: usize) -> GameMap {
+        let mut map = GameMap::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    map.set_tile(x, y, 1);
+                }
+            }
+        }
+        map
+    }
+
+    fn mirror_room() -> GameMap {
+        let mut map = room(10, 5);
+        map.mirrors.insert(MIRROR_TILE, Mirror { color: [0; 3], reflectivity: 1.0 });
+        map
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.001
+    }
+
+    #[test]
+    fn rays_stop_on_open_wrapping_maps() {
+        let mut map = GameMap::new(4, 4);
+        map.out_of_bounds = OutOfBounds::Wrap;
+        let start = Vec2f::new(1.5, 1.5);
+        for max_distance in [f32::INFINITY, f32::NAN, f32::MAX, -1.0] {
+            assert!(cast_ray(&start, Vec2f::new(1.0, 0.3), &map, max_distance).is_none());
+        }
+
+        map.set_tile(3, 1, 1);
+        let hit = cast_ray(&start, Vec2f::new(1.0, 0.0), &map, f32::INFINITY).unwrap();
+        assert_eq!((hit.map_x, hit.map_y), (3, 1));
+        assert!((hit.distance - 1.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn mirrors_reflect_onto_the_wall_behind_the_viewer() {
+        let mut map = mirror_room();
+        map.set_tile(8, 2, MIRROR_TILE);
+        let hit = cast_ray(&Vec2f::new(4.5, 2.5), EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        assert_eq!(hit.bounces, 1);
+        assert_eq!((hit.map_x, hit.map_y, hit.face, hit.wall_type), (0, 2, Face::East, 1));
+        // 3.5 to the mirror, then 7 back across the room
+        assert!(close(hit.distance, 10.5), "{}", hit.distance);
+        assert!(close(hit.point.x, 1.0) && close(hit.point.y, 2.5));
+
+        let mut reflections = Vec::new();
+        cast_ray_with(&Vec2f::new(4.5, 2.5), EAST, &map, DEFAULT_MAX_RAY_DISTANCE, |step| {
+            if let RayStep::Reflect { point } = step {
+                reflections.push(point);
+            }
+        });
+        assert_eq!(reflections.len(), 1);
+        assert!(close(reflections[0].x, 8.0) && close(reflections[0].y, 2.5));
+    }
+
+    #[test]
+    fn facing_mirrors_stop_after_the_bounce_limit() {
+        let mut map = mirror_room();
+        map.set_tile(0, 2, MIRROR_TILE);
+        map.set_tile(9, 2, MIRROR_TILE);
+        let hit = cast_ray(&Vec2f::new(4.5, 2.5), EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        // The last mirror reached is drawn as a plain wall
+        assert_eq!(hit.bounces, MAX_MIRROR_BOUNCES);
+        assert_eq!(hit.wall_type, MIRROR_TILE);
+        assert_eq!(hit.map_x, 9);
+        assert!(close(hit.distance, 4.5 + 8.0 * MAX_MIRROR_BOUNCES as f32), "{}", hit.distance);
+    }
+
+    #[test]
+    fn the_viewer_appears_in_mirrors() {
+        let mut map = mirror_room();
+        map.set_tile(8, 2, MIRROR_TILE);
+        let start = Vec2f::new(4.5, 2.5);
+        let avatar = cast_ray(&start, EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap().avatar.unwrap();
+        // To the mirror and back to the near side of the viewer's body
+        assert!(close(avatar.distance, 3.5 + 3.5 - AVATAR_RADIUS), "{}", avatar.distance);
+
+        // Off to the side, the reflection misses them
+        let aside = cast_ray(&start, Vec2f::new(1.0, 0.1), &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        assert_eq!(aside.bounces, 1);
+        assert!(aside.avatar.is_none());
+
+        // A plain wall shows no one
+        map.mirrors.clear();
+        let plain = cast_ray(&start, EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        assert_eq!((plain.bounces, plain.map_x), (0, 8));
+        assert!(plain.avatar.is_none());
+    }
+
+    #[test]
+    fn rays_continue_through_linked_portals() {
+        // Entering (5, 2) from the west leads out of (9, 3) heading south
+        let mut map = room(12, 6);
+        map.set_tile(5, 2, 2);
+        map.set_tile(9, 3, 2);
+        map.link_portals(PortalFace::new(5, 2, Face::West), PortalFace::new(9, 3, Face::South));
+
+        let mut portals = Vec::new();
+        let start = Vec2f::new(2.5, 2.5);
+        let hit = cast_ray_with(&start, EAST, &map, DEFAULT_MAX_RAY_DISTANCE, |step| {
+            if let RayStep::Portal { enter, exit, rotation } = step {
+                portals.push((enter, exit, rotation));
+            }
+        }).unwrap();
+
+        assert_eq!(portals.len(), 1);
+        let (enter, exit, rotation) = portals[0];
+        assert!(close(enter.x, 5.0) && close(enter.y, 2.5));
+        assert!(close(exit.x, 9.5) && close(exit.y, 4.0));
+        assert!(close(Vec2f::from_angle(rotation).y, 1.0));
+
+        // 2.5 to the portal, then 1 from the exit to the south wall
+        assert_eq!(hit.portals, 1);
+        assert_eq!((hit.map_x, hit.map_y, hit.face, hit.wall_type), (9, 5, Face::North, 1));
+        assert!(close(hit.point.x, 9.5) && close(hit.point.y, 5.0));
+        assert!(close(hit.distance, 3.5), "{}", hit.distance);
+
+        // Walking into the portal lands where the ray went
+        let transit = step_through_portal(&map, Vec2f::new(4.8, 2.5), Vec2f::new(0.4, 0.0)).unwrap();
+        assert!(close(transit.position.x, 9.5) && close(transit.position.y, 4.2));
+        assert!(close(transit.rotation, rotation));
+    }
+
+    #[test]
+    fn portal_loops_stop_at_the_depth_limit() {
+        // Each end of the corridor leads back to the other, heading the same way
+        let mut map = room(10, 5);
+        map.link_portals(PortalFace::new(9, 2, Face::West), PortalFace::new(0, 2, Face::East));
+
+        let hit = cast_ray(&Vec2f::new(4.5, 2.5), EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        // The portal reached last is drawn as a plain wall
+        assert_eq!(hit.portals, MAX_PORTAL_DEPTH);
+        assert_eq!((hit.map_x, hit.map_y, hit.face), (9, 2, Face::West));
+        // Every exit is nudged off its face, so allow for a little lost each time
+        assert!((hit.distance - (4.5 + 8.0 * MAX_PORTAL_DEPTH as f32)).abs() < 0.01, "{}", hit.distance);
+    }
+
+    #[test]
+    fn diagonal_thin_walls_are_hit_inside_their_cell() {
+        let mut map = room(10, 5);
+        map.add_segment(4, 2, WallSegment::new(Vec2f::new(0.0, 0.0), Vec2f::new(1.0, 1.0), 3));
+
+        // Enters the cell at x = 4 and meets the diagonal halfway across
+        let hit = cast_ray(&Vec2f::new(2.5, 2.5), EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        assert!(hit.thin);
+        assert_eq!((hit.map_x, hit.map_y, hit.wall_type), (4, 2, 3));
+        assert!(close(hit.distance, 2.0), "{}", hit.distance);
+        assert!(close(hit.point.x, 4.5) && close(hit.point.y, 2.5));
+        assert!(close(hit.texture_u, std::f32::consts::SQRT_2 / 2.0), "{}", hit.texture_u);
+        assert!(hit.normal.dot(&EAST) < 0.0);
+    }
+
+    #[test]
+    fn rays_beside_a_thin_wall_miss_it() {
+        let mut map = room(10, 5);
+        map.add_segment(4, 2, WallSegment::new(Vec2f::new(0.5, 0.1), Vec2f::new(0.5, 0.6), 3));
+
+        let beside = cast_ray(&Vec2f::new(2.5, 2.8), EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        assert!(!beside.thin);
+        assert_eq!((beside.map_x, beside.wall_type), (9, 1));
+        assert!(close(beside.distance, 6.5), "{}", beside.distance);
+
+        // Texture u runs from the segment's first end
+        let hit = cast_ray(&Vec2f::new(2.5, 2.3), EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        assert!(hit.thin && close(hit.distance, 2.0));
+        assert!(close(hit.texture_u, 0.2), "{}", hit.texture_u);
+
+        map.segments.clear();
+        map.add_segment(4, 2, WallSegment::new(Vec2f::new(0.5, 0.6), Vec2f::new(0.5, 0.1), 3));
+        let reversed = cast_ray(&Vec2f::new(2.5, 2.3), EAST, &map, DEFAULT_MAX_RAY_DISTANCE).unwrap();
+        assert!(close(reversed.texture_u, 0.3), "{}", reversed.texture_u);
+    }
+}
@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use super::actions::{Action, ActionState};
+use super::canvas::{PixelCanvas, CanvasPass, CANVAS_WIDTH};
+use super::hud::HudFont;
+use super::map::GameMap;
+use super::math::Vec2f;
+use super::player::PlayerView;
+use super::raycast::{cast_ray_with, column_ray_dir, get_wall_color, render_3d_view, RayHit, RayStep, RaycastSettings};
+use super::text::{BitmapFont, TextStyle};
+
+pub struct RayDebugPlugin;
+
+impl Plugin for RayDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(RayDebugView::default())
+            .add_systems(Update, (
+                handle_ray_debug_controls,
+                draw_ray_debug_view.in_set(CanvasPass::World).after(render_3d_view),
+            ));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct RayDebugView {
+    pub enabled: bool,
+    pub selected_column: Option<u32>,
+    pub step: usize, // DDA steps shown for the selected column
+}
+
+fn handle_ray_debug_controls(
+    actions: Res<ActionState>,
+    mut view: ResMut<RayDebugView>,
+) {
+    if actions.just_pressed(Action::ToggleRayView) {
+        view.enabled = !view.enabled;
+        info!("Ray debug view: {}", if view.enabled { "ON" } else { "OFF" });
+    }
+    if !view.enabled {
+        return;
+    }
+
+    let column_step = |column: Option<u32>, delta: i32| {
+        let column = column.unwrap_or(CANVAS_WIDTH / 2) as i32 + delta;
+        Some(column.clamp(0, CANVAS_WIDTH as i32 - 1) as u32)
+    };
+
+    if actions.pressed(Action::RayViewPrevColumn) {
+        view.selected_column = column_step(view.selected_column, -1);
+        view.step = 0;
+    }
+    if actions.pressed(Action::RayViewNextColumn) {
+        view.selected_column = column_step(view.selected_column, 1);
+        view.step = 0;
+    }
+    if actions.just_pressed(Action::RayViewStep) && view.selected_column.is_some() {
+        view.step += 1;
+    }
+    if actions.just_pressed(Action::RayViewStepBack) {
+        if view.step == 0 {
+            view.selected_column = None; // Back to the overview
+        }
+        view.step = view.step.saturating_sub(1);
+    }
+}
+
+struct TopDown {
+    scale: f32,
+    offset_x: f32,
+    offset_y: f32,
+}
+
+impl TopDown {
+    fn to_screen(&self, point: Vec2f) -> (i32, i32) {
+        (
+            (self.offset_x + point.x * self.scale) as i32,
+            (self.offset_y + point.y * self.scale) as i32,
+        )
+    }
+
+    fn fill_cell(&self, canvas: &mut PixelCanvas, x: i32, y: i32, color: [u8; 4]) {
+        let (left, top) = self.to_screen(Vec2f::new(x as f32, y as f32));
+        let (right, bottom) = self.to_screen(Vec2f::new((x + 1) as f32, (y + 1) as f32));
+        canvas.fill_rect(left, top, (right - left).max(1), (bottom - top).max(1), color);
+    }
+}
+
+fn draw_ray_debug_view(
+    mut canvas: ResMut<PixelCanvas>,
+    view: Res<RayDebugView>,
+    player: Res<PlayerView>,
+    map: Res<GameMap>,
+    settings: Res<RaycastSettings>,
+    font: Res<HudFont>,
+) {
+    if !view.enabled {
+        return;
+    }
+
+    // Fit the whole map to the canvas, centered
+    let scale = (canvas.width as f32 / map.width as f32).min(canvas.height as f32 / map.height as f32);
+    let top_down = TopDown {
+        scale,
+        offset_x: (canvas.width as f32 - map.width as f32 * scale) / 2.0,
+        offset_y: (canvas.height as f32 - map.height as f32 * scale) / 2.0,
+    };
+
+    canvas.clear([0, 0, 0, 255]);
+    for y in 0..canvas.height {
+        for x in 0..canvas.width {
+            let map_x = (x as f32 - top_down.offset_x) / scale;
+            let map_y = (y as f32 - top_down.offset_y) / scale;
+            if map_x < 0.0 || map_y < 0.0 || map_x >= map.width as f32 || map_y >= map.height as f32 {
+                continue;
+            }
+
+            let tile = map.get_tile(map_x as usize, map_y as usize);
+            let color = if tile == 0 { [30, 30, 30, 255] } else { get_wall_color(tile, false) };
+            canvas.set_pixel(x, y, [color[0] / 2, color[1] / 2, color[2] / 2, 255]);
+        }
+    }
+
+    draw_thin_walls(&mut canvas, &top_down, &map);
+    draw_portals(&mut canvas, &top_down, &map);
+    match view.selected_column {
+        None => draw_overview(&mut canvas, &top_down, &player, &map, settings.max_distance),
+        Some(column) => draw_traversal(
+            &mut canvas, &top_down, &player, &map, settings.max_distance, column, view.step, &font.0,
+        ),
+    }
+
+    let origin = top_down.to_screen(player.position);
+    canvas.fill_circle(origin.0, origin.1, 2, [255, 255, 255, 255]);
+}
+
+// A ray's traversal as the debug view draws it
+struct TracedRay {
+    cells: Vec<(i32, i32)>,
+    segments: Vec<(Vec2f, Vec2f)>, // One per straight stretch between portals and mirrors
+    hit: Option<RayHit>,
+}
+
+fn trace_ray(player: &PlayerView, column: u32, map: &GameMap, max_distance: f32) -> TracedRay {
+    let direction = column_ray_dir(player, column, CANVAS_WIDTH);
+    let mut cells = Vec::new();
+    let mut segments = Vec::new();
+    let mut segment_start = player.position;
+
+    let hit = cast_ray_with(&player.position, direction, map, max_distance, |step| match step {
+        RayStep::Cell(cell_x, cell_y) => cells.push((cell_x, cell_y)),
+        RayStep::Portal { enter, exit, .. } => {
+            segments.push((segment_start, enter));
+            segment_start = exit;
+        }
+        RayStep::Reflect { point } => {
+            segments.push((segment_start, point));
+            segment_start = point;
+        }
+    });
+
+    // Missed: end at the center of the last cell the DDA reached
+    let end = match &hit {
+        Some(hit) => hit.point,
+        None => cells.last().map_or(segment_start, |&(cell_x, cell_y)| {
+            Vec2f::new(cell_x as f32 + 0.5, cell_y as f32 + 0.5)
+        }),
+    };
+    segments.push((segment_start, end));
+
+    TracedRay { cells, segments, hit }
+}
+
+fn draw_segments(canvas: &mut PixelCanvas, top_down: &TopDown, segments: &[(Vec2f, Vec2f)], color: [u8; 4]) {
+    for &(from, to) in segments {
+        let from = top_down.to_screen(from);
+        let to = top_down.to_screen(to);
+        canvas.draw_line(from.0, from.1, to.0, to.1, color);
+    }
+}
+
+fn draw_thin_walls(canvas: &mut PixelCanvas, top_down: &TopDown, map: &GameMap) {
+    for (&(cell_x, cell_y), segments) in &map.segments {
+        for segment in segments.iter().map(|segment| segment.in_cell(cell_x, cell_y)) {
+            let color = get_wall_color(segment.wall_type, false);
+            draw_segments(canvas, top_down, &[(segment.a, segment.b)], color);
+        }
+    }
+}
+
+fn draw_portals(canvas: &mut PixelCanvas, top_down: &TopDown, map: &GameMap) {
+    for face in map.portals.iter().flat_map(|link| [link.a, link.b]) {
+        let center = face.center();
+        let along = face.face.normal().rotate(std::f32::consts::PI / 2.0) * 0.5;
+        let from = top_down.to_screen(center - along);
+        let to = top_down.to_screen(center + along);
+        canvas.draw_thick_line(from.0, from.1, to.0, to.1, 2.0, [0, 255, 255, 255]);
+    }
+}
+
+fn draw_overview(canvas: &mut PixelCanvas, top_down: &TopDown, player: &PlayerView, map: &GameMap, max_distance: f32) {
+    // Every column's ray, the same ones the 3D view casts
+    let rays: Vec<TracedRay> = (0..CANVAS_WIDTH)
+        .map(|x| trace_ray(player, x, map, max_distance))
+        .collect();
+
+    let mut visited: Vec<(i32, i32)> = rays.iter()
+        .flat_map(|ray| ray.cells.iter().map(|&(x, y)| map.wrap_cell(x, y)))
+        .collect();
+    visited.sort_unstable();
+    visited.dedup();
+    for (cell_x, cell_y) in visited {
+        top_down.fill_cell(canvas, cell_x, cell_y, [255, 255, 255, 40]);
+    }
+
+    for ray in &rays {
+        let color = match &ray.hit {
+            Some(hit) => get_wall_color(hit.wall_type, hit.side),
+            None => [255, 0, 0, 255], // Lost rays stand out in red
+        };
+        draw_segments(canvas, top_down, &ray.segments, color);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_traversal(
+    canvas: &mut PixelCanvas,
+    top_down: &TopDown,
+    player: &PlayerView,
+    map: &GameMap,
+    max_distance: f32,
+    column: u32,
+    step: usize,
+    font: &BitmapFont,
+) {
+    let TracedRay { cells, segments, hit } = trace_ray(player, column, map, max_distance);
+
+    let shown = step.min(cells.len());
+    for (index, &(cell_x, cell_y)) in cells.iter().enumerate() {
+        let color = if index + 1 == shown {
+            [255, 160, 0, 200] // Current step
+        } else if index < shown {
+            [255, 160, 0, 90]
+        } else {
+            [255, 255, 255, 25]
+        };
+        let (cell_x, cell_y) = map.wrap_cell(cell_x, cell_y);
+        top_down.fill_cell(canvas, cell_x, cell_y, color);
+    }
+
+    draw_segments(canvas, top_down, &segments, [255, 255, 0, 255]);
+
+    let result = match &hit {
+        Some(hit) => format!("hit {} {} at ({}, {}), dist {:.3}, {} face, u {:.2}, {} portals, {} bounces",
+            if hit.thin { "thin wall" } else { "tile" }, hit.wall_type, hit.map_x, hit.map_y, hit.distance,
+            hit.face.name(), hit.texture_u, hit.portals, hit.bounces),
+        None => "{red}no hit{/}".to_string(),
+    };
+    let current = match shown.checked_sub(1).and_then(|index| cells.get(index)) {
+        Some((cell_x, cell_y)) => format!("cell ({}, {})", cell_x, cell_y),
+        None => "start".to_string(),
+    };
+    let text = format!("Column {}  step {}/{}  {}\n{}", column, shown, cells.len(), current, result);
+    canvas.draw_text(font, &text, 4, canvas.height as i32 - 24, &TextStyle::default());
+}
@@ -1,7 +1,13 @@
 use bevy::prelude::*;
-use super::canvas::PixelCanvas;
-use super::player::Player;
-use super::map::GameMap;
+use std::time::Instant;
+use super::canvas::{PixelCanvas, CanvasPass};
+use super::player::PlayerView;
+use super::raycast::RenderStats;
+use super::map::{GameMap, OutOfBounds};
+use super::actions::{Action, ActionState};
+
+// Maps larger than this many cells across only show the area around the player
+const MINIMAP_MAX_CELLS: usize = 32;
 
 #[derive(Resource)]
 pub struct RenderSettings {
@@ -24,16 +30,16 @@ impl Plugin for RenderPlugin {
             .insert_resource(RenderSettings::default())
             .add_systems(Update, (
                 toggle_minimap,
-                render_minimap,
+                render_minimap.in_set(CanvasPass::Overlay),
             ));
     }
 }
 
 fn toggle_minimap(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut settings: ResMut<RenderSettings>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::KeyM) {
+    if actions.just_pressed(Action::ToggleMinimap) {
         settings.show_minimap = !settings.show_minimap;
         info!("Minimap: {}", if settings.show_minimap { "ON" } else { "OFF" });
     }
@@ -41,9 +47,10 @@ fn toggle_minimap(
 
 fn render_minimap(
     mut canvas: ResMut<PixelCanvas>,
-    player: Res<Player>,
+    player: Res<PlayerView>,
     map: Option<Res<GameMap>>,
     settings: Res<RenderSettings>,
+    mut stats: ResMut<RenderStats>,
 ) {
     if !settings.show_minimap {
         return;
@@ -51,50 +58,60 @@ fn render_minimap(
     
     let Some(map) = map else { return };
     
-    let minimap_size = 100;
-    let minimap_scale = 4;
-    let start_x = (canvas.width - minimap_size) as i32;
+    let started = Instant::now();
+    let minimap_size = 100i32;
+    let start_x = canvas.width as i32 - minimap_size - 10;
     let start_y = 10i32;
     
+    // Small maps are shown whole; large ones as a window around the player.
+    // Wrapping maps are always centered on the player and tile across the seam.
+    let view_cells = map.width.max(map.height).min(MINIMAP_MAX_CELLS) as f32;
+    let cell_size = minimap_size as f32 / view_cells;
+    let wraps = map.out_of_bounds == OutOfBounds::Wrap;
+    let (origin_x, origin_y) = if wraps {
+        (player.position.x - view_cells / 2.0, player.position.y - view_cells / 2.0)
+    } else {
+        (
+            (player.position.x - view_cells / 2.0).clamp(0.0, (map.width as f32 - view_cells).max(0.0)),
+            (player.position.y - view_cells / 2.0).clamp(0.0, (map.height as f32 - view_cells).max(0.0)),
+        )
+    };
+    
     // Clear minimap area with border
-    for y in 0..minimap_size + 2 {
-        for x in 0..minimap_size + 2 {
-            let px = (start_x - 1 + x as i32) as u32;
-            let py = (start_y - 1 + y as i32) as u32;
-            if px < canvas.width && py < canvas.height {
-                canvas.set_pixel(px, py, [0, 0, 0, 255]);
-            }
-        }
-    }
+    canvas.fill_rect(start_x - 1, start_y - 1, minimap_size + 2, minimap_size + 2, [0, 0, 0, 255]);
     
-    // Draw map tiles
-    for map_y in 0..map.height {
-        for map_x in 0..map.width {
-            let tile = map.get_tile(map_x, map_y);
-            let color = match tile {
+    // Sample one tile per minimap pixel, so the cost doesn't grow with the map
+    for pixel_y in 0..minimap_size {
+        for pixel_x in 0..minimap_size {
+            let map_x = (origin_x + pixel_x as f32 / cell_size).floor() as i32;
+            let map_y = (origin_y + pixel_y as f32 / cell_size).floor() as i32;
+            if !wraps && !map.in_bounds(map_x, map_y) {
+                continue;
+            }
+            
+            let color = match map.tile_at(map_x, map_y) {
                 0 => [40, 40, 40, 255],   // Floor - dark gray
                 1 => [255, 255, 255, 255], // Wall - white
                 2 => [0, 255, 0, 255],     // Green wall
                 3 => [0, 0, 255, 255],     // Blue wall
                 4 => [255, 255, 0, 255],   // Yellow wall
                 5 => [255, 0, 255, 255],   // Magenta wall
+                6 => [170, 200, 220, 255], // Mirror - silver
                 _ => [128, 128, 128, 255], // Unknown - gray
             };
-            
-            let pixel_x = start_x + (map_x * minimap_scale / map.width) as i32;
-            let pixel_y = start_y + (map_y * minimap_scale / map.height) as i32;
-            
-            if pixel_x >= 0 && pixel_y >= 0 {
-                canvas.set_pixel(pixel_x as u32, pixel_y as u32, color);
-            }
+            canvas.set_pixel((start_x + pixel_x) as u32, (start_y + pixel_y) as u32, color);
         }
     }
     
-    // Draw player position
-    let player_x = start_x + (player.position.x * minimap_scale as f32 / map.width as f32) as i32;
-    let player_y = start_y + (player.position.y * minimap_scale as f32 / map.height as f32) as i32;
+    // Draw player position and facing
+    let player_x = start_x + ((player.position.x - origin_x) * cell_size) as i32;
+    let player_y = start_y + ((player.position.y - origin_y) * cell_size) as i32;
+    let facing_length = cell_size.max(2.0) * 2.0;
+    let facing_x = player_x + (player.direction.x * facing_length) as i32;
+    let facing_y = player_y + (player.direction.y * facing_length) as i32;
     
-    if player_x >= 0 && player_y >= 0 {
-        canvas.set_pixel(player_x as u32, player_y as u32, [255, 0, 0, 255]); // Red dot for player
-    }
-}
\ No newline at end of file
+    canvas.draw_line(player_x, player_y, facing_x, facing_y, [255, 128, 128, 255]);
+    canvas.fill_circle(player_x, player_y, 1, [255, 0, 0, 255]); // Red dot for player
+    
+    stats.record_pass("minimap", started);
+}
@@ -0,0 +1,385 @@
+use bevy::prelude::*;
+use std::fmt::Write as _;
+use super::actions::{Action, ActionState};
+use super::map::GameMap;
+use super::math::{Vec2f, lerp};
+use super::surface::AnimationClock;
+use super::player::{
+    Player, PlayerInput, PlayerPose, PlayerSystems, PlayerView, StepInput, CROUCH_EYE_HEIGHT, STAND_EYE_HEIGHT,
+    simulate_player,
+};
+
+pub const REPLAY_PATH: &str = "replay.rec";
+const REPLAY_HEADER: &str = "RAYCASTER_REPLAY 1";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(ReplayState::default())
+            .add_systems(Update, handle_replay_controls.before(PlayerSystems::Interpolate))
+            .add_systems(FixedUpdate, (
+                drive_replay,
+                record_input,
+            ).chain().after(PlayerSystems::PrepareStep).before(PlayerSystems::Simulate));
+    }
+}
+
+// The starting pose plus the input of every fixed tick. Replaying the frames
+// through simulate_player from the same state reproduces the same trajectory.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub start: PlayerPose,
+    pub velocity: Vec2f, // Momentum at the start, which the pose doesn't carry
+    pub stamina: f32,
+    pub winded: bool,
+    // Stance at the start: crouching changes top speed and being in the air
+    // changes grip, so a recording can begin mid-jump or crouched
+    pub crouch: f32,
+    pub jump_height: f32,
+    pub vertical_speed: f32,
+    pub bob_phase: f32,
+    pub bob_amount: f32,
+    pub clock: f32, // Animation clock at the start, so animations replay in step too
+    pub end: Option<PlayerPose>,
+    pub frames: Vec<PlayerInput>,
+}
+
+impl Recording {
+    pub fn new(player: &Player, clock: f32) -> Self {
+        Self {
+            start: player.pose(),
+            velocity: player.velocity,
+            stamina: player.stamina,
+            winded: player.winded,
+            crouch: player.crouch,
+            jump_height: player.jump_height,
+            vertical_speed: player.vertical_speed,
+            bob_phase: player.bob_phase,
+            bob_amount: player.bob_amount,
+            clock,
+            end: None,
+            frames: Vec::new(),
+        }
+    }
+
+    // Puts the player where and how it was when recording started
+    pub fn begin(&self, player: &mut Player) {
+        player.set_pose(self.start);
+        player.velocity = self.velocity;
+        player.stamina = self.stamina;
+        player.winded = self.winded;
+        player.crouch = self.crouch;
+        player.jump_height = self.jump_height;
+        player.vertical_speed = self.vertical_speed;
+        player.bob_phase = self.bob_phase;
+        player.bob_amount = self.bob_amount;
+        player.eye_height = lerp(STAND_EYE_HEIGHT, CROUCH_EYE_HEIGHT, self.crouch) + self.jump_height;
+    }
+
+    // f32 Display output is the shortest string that parses back to the
+    // same value, so the text format round-trips exactly
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "{}", REPLAY_HEADER);
+        let _ = writeln!(text, "start {}", format_pose(&self.start));
+        let _ = writeln!(text, "motion {} {} {} {}", self.velocity.x, self.velocity.y, self.stamina, self.winded as u8);
+        let _ = writeln!(text, "body {} {} {} {} {}",
+            self.crouch, self.jump_height, self.vertical_speed, self.bob_phase, self.bob_amount);
+        let _ = writeln!(text, "clock {}", self.clock);
+        if let Some(end) = &self.end {
+            let _ = writeln!(text, "end {}", format_pose(end));
+        }
+        for frame in &self.frames {
+            let _ = writeln!(text, "frame {} {} {} {} {} {} {} {} {}",
+                frame.dt, frame.forward, frame.strafe, frame.turn, frame.look_yaw, frame.look_pitch,
+                frame.jump as u8, frame.crouch as u8, frame.sprint as u8);
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Recording, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().is_none_or(|(_, header)| header.trim() != REPLAY_HEADER) {
+            return Err(format!("missing '{}' header", REPLAY_HEADER));
+        }
+
+        let mut start = None;
+        let mut clock = None;
+        let mut motion = None;
+        let mut body = None;
+        let mut end = None;
+        let mut frames = Vec::new();
+
+        for (line_number, line) in lines {
+            let mut parts = line.split_whitespace();
+            let Some(kind) = parts.next() else { continue };
+            let values = parts
+                .map(|value| value.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|err| format!("line {}: {}", line_number + 1, err))?;
+
+            match (kind, values.as_slice()) {
+                ("start", [x, y, angle, pitch]) => start = Some(make_pose(*x, *y, *angle, *pitch)),
+                ("clock", [seconds]) => clock = Some(*seconds),
+                ("body", [crouch, jump_height, vertical_speed, bob_phase, bob_amount]) =>
+                    body = Some([*crouch, *jump_height, *vertical_speed, *bob_phase, *bob_amount]),
+                ("motion", [vx, vy, stamina, winded]) => motion = Some((Vec2f::new(*vx, *vy), *stamina, *winded != 0.0)),
+                ("end", [x, y, angle, pitch]) => end = Some(make_pose(*x, *y, *angle, *pitch)),
+                ("frame", [dt, forward, strafe, turn, look_yaw, look_pitch, jump, crouch, sprint]) => {
+                    frames.push(PlayerInput {
+                        dt: *dt,
+                        forward: *forward,
+                        strafe: *strafe,
+                        turn: *turn,
+                        look_yaw: *look_yaw,
+                        look_pitch: *look_pitch,
+                        jump: *jump != 0.0,
+                        crouch: *crouch != 0.0,
+                        sprint: *sprint != 0.0,
+                    })
+                }
+                _ => return Err(format!("line {}: malformed '{}' entry", line_number + 1, kind)),
+            }
+        }
+
+        let missing = |what: &str| format!("missing {}", what);
+        let start = start.ok_or_else(|| missing("start pose"))?;
+        let (velocity, stamina, winded) = motion.ok_or_else(|| missing("motion"))?;
+        let [crouch, jump_height, vertical_speed, bob_phase, bob_amount] = body.ok_or_else(|| missing("body"))?;
+        let clock = clock.ok_or_else(|| missing("clock"))?;
+        Ok(Recording {
+            start, velocity, stamina, winded, crouch, jump_height, vertical_speed, bob_phase, bob_amount, clock, end, frames,
+        })
+    }
+}
+
+fn format_pose(pose: &PlayerPose) -> String {
+    format!("{} {} {} {}", pose.position.x, pose.position.y, pose.angle, pose.pitch)
+}
+
+fn make_pose(x: f32, y: f32, angle: f32, pitch: f32) -> PlayerPose {
+    PlayerPose {
+        position: Vec2f::new(x, y),
+        angle,
+        pitch,
+    }
+}
+
+// Runs a recording without any Bevy app, e.g. to assert final positions
+pub fn replay_headless(recording: &Recording, map: Option<&GameMap>) -> Player {
+    let mut player = Player::default();
+    recording.begin(&mut player);
+    for frame in &recording.frames {
+        simulate_player(&mut player, map, frame);
+    }
+    player
+}
+
+#[derive(Resource, Default)]
+pub enum ReplayState {
+    #[default]
+    Idle,
+    Recording(Recording),
+    Playing {
+        recording: Recording,
+        cursor: usize,
+    },
+}
+
+fn handle_replay_controls(
+    actions: Res<ActionState>,
+    mut state: ResMut<ReplayState>,
+    mut player: ResMut<Player>,
+    mut view: ResMut<PlayerView>,
+    mut clock: ResMut<AnimationClock>,
+) {
+    if actions.just_pressed(Action::ToggleRecording) {
+        match std::mem::take(&mut *state) {
+            ReplayState::Idle => {
+                *state = ReplayState::Recording(Recording::new(&player, clock.seconds));
+                info!("Recording input to {}", REPLAY_PATH);
+            }
+            ReplayState::Recording(mut recording) => {
+                recording.end = Some(player.pose());
+                match std::fs::write(REPLAY_PATH, recording.to_text()) {
+                    Ok(()) => info!("Recorded {} frames to {}", recording.frames.len(), REPLAY_PATH),
+                    Err(err) => warn!("Could not write {}: {}", REPLAY_PATH, err),
+                }
+            }
+            playing => {
+                *state = playing;
+                warn!("Cannot record while a replay is playing");
+            }
+        }
+    }
+
+    if actions.just_pressed(Action::PlayReplay) && matches!(*state, ReplayState::Idle) {
+        let recording = std::fs::read_to_string(REPLAY_PATH)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Recording::from_text(&text));
+
+        match recording {
+            Ok(recording) => {
+                info!("Replaying {} frames from {}", recording.frames.len(), REPLAY_PATH);
+                recording.begin(&mut player);
+                view.snap_to(recording.start);
+                clock.seconds = recording.clock;
+                *state = ReplayState::Playing { recording, cursor: 0 };
+            }
+            Err(err) => warn!("Could not load {}: {}", REPLAY_PATH, err),
+        }
+    }
+}
+
+fn drive_replay(
+    mut state: ResMut<ReplayState>,
+    mut step: ResMut<StepInput>,
+    player: Res<Player>,
+) {
+    let ReplayState::Playing { recording, cursor } = &mut *state else { return };
+
+    if let Some(frame) = recording.frames.get(*cursor) {
+        // Live input is discarded while a replay drives the player
+        step.0 = *frame;
+        *cursor += 1;
+        return;
+    }
+
+    match recording.end {
+        Some(end) if end == player.pose() => info!("Replay finished - final pose matches recording"),
+        Some(end) => warn!("Replay finished - final pose diverged: expected ({}, {}), got ({}, {})",
+            end.position.x, end.position.y, player.position.x, player.position.y),
+        None => info!("Replay finished"),
+    }
+    *state = ReplayState::Idle;
+}
+
+fn record_input(
+    mut state: ResMut<ReplayState>,
+    step: Res<StepInput>,
+) {
+    if let ReplayState::Recording(recording) = &mut *state {
+        recording.frames.push(step.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // Walled 8x8 room with one pillar, so some frames run into a wall
+    fn test_map() -> GameMap {
+        let mut map = GameMap::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                if x == 0 || y == 0 || x == 7 || y == 7 || (x, y) == (5, 3) {
+                    map.set_tile(x, y, 1);
+                }
+            }
+        }
+        map
+    }
+
+    fn frame(forward: f32, strafe: f32, turn: f32) -> PlayerInput {
+        PlayerInput {
+            dt: DT,
+            forward,
+            strafe,
+            turn,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn headless_replay_ends_at_recorded_pose() {
+        let map = test_map();
+        let mut player = Player::default();
+        player.set_pose(make_pose(2.5, 2.5, 0.0, 0.0));
+
+        let mut recording = Recording::new(&player, 0.0);
+        let inputs = (0..40).map(|_| frame(1.0, 0.0, 0.0))
+            .chain((0..20).map(|_| frame(0.5, 1.0, 0.3)))
+            .chain((0..30).map(|_| PlayerInput { sprint: true, ..frame(1.0, 0.0, -0.2) }))
+            .chain((0..20).map(|_| frame(0.0, 0.0, 0.0)));
+        for input in inputs {
+            simulate_player(&mut player, Some(&map), &input);
+            recording.frames.push(input);
+        }
+        recording.end = Some(player.pose());
+
+        let replayed = replay_headless(&recording, Some(&map));
+        assert_eq!(Some(replayed.pose()), recording.end);
+
+        let reloaded = Recording::from_text(&recording.to_text()).unwrap();
+        assert_eq!(Some(replay_headless(&reloaded, Some(&map)).pose()), recording.end);
+    }
+
+    #[test]
+    fn recordings_can_start_mid_jump_or_crouched() {
+        let map = test_map();
+        let run_up = [
+            PlayerInput { jump: true, ..frame(1.0, 0.0, 0.0) },
+            PlayerInput { crouch: true, ..frame(1.0, 0.0, 0.0) },
+        ];
+        for run_up in run_up {
+            let mut player = Player::default();
+            player.set_pose(make_pose(2.5, 2.5, 0.3, 0.0));
+            for _ in 0..8 {
+                simulate_player(&mut player, Some(&map), &run_up);
+            }
+            assert!(player.jump_height > 0.0 || player.crouch > 0.0);
+
+            let mut recording = Recording::new(&player, 0.0);
+            for _ in 0..60 {
+                let input = frame(1.0, 0.3, 0.1);
+                simulate_player(&mut player, Some(&map), &input);
+                recording.frames.push(input);
+            }
+            recording.end = Some(player.pose());
+
+            assert_eq!(Some(replay_headless(&recording, Some(&map)).pose()), recording.end);
+            let reloaded = Recording::from_text(&recording.to_text()).unwrap();
+            assert_eq!(reloaded.jump_height, recording.jump_height);
+            assert_eq!(reloaded.crouch, recording.crouch);
+            assert_eq!(Some(replay_headless(&reloaded, Some(&map)).pose()), recording.end);
+        }
+    }
+
+    #[test]
+    fn text_round_trip_and_bad_files() {
+        let mut player = Player::default();
+        player.set_pose(make_pose(3.25, 4.5, 1.0, 0.1));
+        player.velocity = Vec2f::new(0.5, -0.25);
+        player.stamina = 0.4;
+        let mut recording = Recording::new(&player, 12.5);
+        recording.frames.push(PlayerInput { jump: true, look_yaw: 0.01, look_pitch: -0.02, ..frame(1.0, -1.0, 0.5) });
+        recording.frames.push(PlayerInput { crouch: true, sprint: true, ..frame(0.25, 0.0, 0.0) });
+        recording.end = Some(make_pose(4.0, 4.0, 1.5, 0.0));
+
+        let parsed = Recording::from_text(&recording.to_text()).unwrap();
+        assert_eq!(parsed.start, recording.start);
+        assert_eq!(parsed.velocity, recording.velocity);
+        assert_eq!(parsed.stamina, recording.stamina);
+        assert_eq!(parsed.winded, recording.winded);
+        assert_eq!(parsed.clock, recording.clock);
+        assert_eq!(parsed.end, recording.end);
+        assert_eq!(parsed.frames, recording.frames);
+
+        let text = recording.to_text();
+        assert!(Recording::from_text(&text.replace(REPLAY_HEADER, "RAYCASTER_REPLAY 9")).is_err());
+        for kind in ["start", "motion", "body", "clock"] {
+            let without: String = text.lines()
+                .filter(|line| !line.starts_with(kind))
+                .map(|line| format!("{}\n", line))
+                .collect();
+            assert!(Recording::from_text(&without).is_err(), "{}", kind);
+        }
+        // Frames carry every input; shorter ones are malformed
+        assert!(Recording::from_text(&format!("{}frame 0.016 1 0 0 0 0.5\n", text)).is_err());
+        assert!(Recording::from_text(&format!("{}frame 0.016 1 0 0 0 0.5 1 1\n", text)).is_err());
+    }
+}
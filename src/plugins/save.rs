@@ -0,0 +1,698 @@
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::actions::{Action, ActionState};
+use super::canvas::{CanvasPass, PixelCanvas};
+use super::draw::is_asset_name;
+use super::hud::HudMessages;
+use super::map::{FloorMaterial, GameMap, Mirror, OutOfBounds};
+use super::math::Vec2f;
+use super::portal::{Face, PortalFace};
+use super::segment::WallSegment;
+use super::lighting::MapLight;
+use super::surface::{WallSurface, MAX_SURFACE_FRAMES};
+use super::player::{Player, PlayerPose, PlayerSystems, PlayerView};
+
+pub const SAVE_DIR: &str = "saves";
+pub const SAVE_SLOT_COUNT: u32 = 4;
+pub const SAVE_VERSION: u32 = 1;
+const SAVE_HEADER: &str = "RAYCASTER_SAVE";
+const THUMBNAIL_SCALE: u32 = 4;
+const MAX_MAP_CELLS: usize = 1 << 24; // Far past any real map, well short of running out of memory
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(SaveSlots::default())
+            .add_systems(Update, (
+                handle_save_controls.before(PlayerSystems::Interpolate),
+                // The thumbnail is taken once this frame is finished, before upload swaps the buffers
+                quick_save.after(CanvasPass::PostProcess).before(CanvasPass::Upload),
+            ));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SaveSlots {
+    pub current: u32,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    MissingHeader,
+    UnsupportedVersion(u32),
+    Parse { line: usize, message: String },
+    Missing(&'static str),
+    Invalid(&'static str),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "{}", err),
+            SaveError::MissingHeader => write!(f, "not a raycaster save file"),
+            SaveError::UnsupportedVersion(version) => write!(
+                f, "save version {} is not supported (current version is {})", version, SAVE_VERSION),
+            SaveError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            SaveError::Missing(what) => write!(f, "save is missing {}", what),
+            SaveError::Invalid(what) => write!(f, "save has an invalid {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+// Section name -> lines of (key, values). Migrations rewrite this before
+// it is interpreted, so old files can be upgraded field by field.
+pub type RawSave = BTreeMap<String, Vec<(String, Vec<String>)>>;
+
+type Migration = fn(&mut RawSave) -> Result<(), SaveError>;
+
+// MIGRATIONS[n] upgrades a version n + 1 save to version n + 2. Bumping
+// SAVE_VERSION means adding the step from the previous version here.
+const MIGRATIONS: &[Migration] = &[];
+
+pub fn migrate(raw: &mut RawSave, mut version: u32) -> Result<(), SaveError> {
+    if version == 0 || version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+
+    while version < SAVE_VERSION {
+        let migration = MIGRATIONS
+            .get(version as usize - 1)
+            .ok_or(SaveError::UnsupportedVersion(version))?;
+        migration(raw)?;
+        version += 1;
+    }
+    Ok(())
+}
+
+pub struct SaveGame {
+    pub saved_at: u64, // Unix seconds
+    pub pose: PlayerPose,
+    pub move_speed: f32,
+    pub rotation_speed: f32,
+    pub mouse_sensitivity: f32,
+    pub map: GameMap,
+}
+
+impl SaveGame {
+    pub fn capture(player: &Player, map: &GameMap) -> Self {
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            saved_at,
+            pose: player.pose(),
+            move_speed: player.move_speed,
+            rotation_speed: player.rotation_speed,
+            mouse_sensitivity: player.mouse_sensitivity,
+            map: map.clone(),
+        }
+    }
+
+    pub fn apply(self, player: &mut Player, map: &mut GameMap) {
+        player.set_pose(self.pose);
+        player.move_speed = self.move_speed;
+        player.rotation_speed = self.rotation_speed;
+        player.mouse_sensitivity = self.mouse_sensitivity;
+        *map = self.map;
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "{}", SAVE_HEADER);
+        let _ = writeln!(text, "version {}", SAVE_VERSION);
+        let _ = writeln!(text, "[meta]");
+        let _ = writeln!(text, "saved_at {}", self.saved_at);
+        let _ = writeln!(text, "[player]");
+        let _ = writeln!(text, "position {} {}", self.pose.position.x, self.pose.position.y);
+        let _ = writeln!(text, "angle {}", self.pose.angle);
+        let _ = writeln!(text, "pitch {}", self.pose.pitch);
+        let _ = writeln!(text, "move_speed {}", self.move_speed);
+        let _ = writeln!(text, "rotation_speed {}", self.rotation_speed);
+        let _ = writeln!(text, "mouse_sensitivity {}", self.mouse_sensitivity);
+        let _ = writeln!(text, "[map]");
+        let _ = writeln!(text, "size {} {}", self.map.width, self.map.height);
+        let _ = writeln!(text, "out_of_bounds {}", self.map.out_of_bounds.name());
+        let _ = writeln!(text, "sky {}", self.map.sky);
+        for link in &self.map.portals {
+            let _ = writeln!(text, "portal {} {} {} {} {} {}",
+                link.a.x, link.a.y, link.a.face.name(), link.b.x, link.b.y, link.b.face.name());
+        }
+        for (wall_type, mirror) in &self.map.mirrors {
+            let [r, g, b] = mirror.color;
+            let _ = writeln!(text, "mirror {} {} {} {} {}", wall_type, r, g, b, mirror.reflectivity);
+        }
+        for (&(x, y), segments) in &self.map.segments {
+            for segment in segments {
+                let _ = writeln!(text, "segment {} {} {} {} {} {} {}",
+                    x, y, segment.a.x, segment.a.y, segment.b.x, segment.b.y, segment.wall_type);
+            }
+        }
+        for (wall_type, surface) in &self.map.surfaces {
+            let _ = writeln!(text, "surface {} {} {} {} {} {}",
+                wall_type, surface.texture, surface.frames, surface.frame_rate, surface.scroll.x, surface.scroll.y);
+        }
+        for (from, to) in &self.map.switches {
+            let _ = writeln!(text, "switch {} {}", from, to);
+        }
+        for (floor_type, material) in &self.map.floor_materials {
+            let [r, g, b] = material.color;
+            let _ = writeln!(text, "floor {} {} {} {} {} {}", floor_type, r, g, b, material.friction, material.speed);
+        }
+        for light in &self.map.lights {
+            let [r, g, b] = light.color;
+            let _ = writeln!(text, "light {} {} {} {} {} {} {} {} {}",
+                light.position.x, light.position.y, r, g, b, light.radius, light.intensity, light.flicker, light.enabled);
+        }
+        for y in 0..self.map.height {
+            let cells: Vec<String> = self.map.row(y).iter().map(u8::to_string).collect();
+            let _ = writeln!(text, "row {}", cells.join(" "));
+        }
+        if !self.map.ceilings.is_empty() {
+            for row in self.map.ceilings.chunks(self.map.width) {
+                let cells: Vec<String> = row.iter().map(u8::to_string).collect();
+                let _ = writeln!(text, "ceiling_row {}", cells.join(" "));
+            }
+        }
+        if !self.map.floors.is_empty() {
+            for row in self.map.floors.chunks(self.map.width) {
+                let cells: Vec<String> = row.iter().map(u8::to_string).collect();
+                let _ = writeln!(text, "floor_row {}", cells.join(" "));
+            }
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<SaveGame, SaveError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, header)) if header.trim() == SAVE_HEADER => {}
+            _ => return Err(SaveError::MissingHeader),
+        }
+
+        let version = match lines.next() {
+            Some((line, text)) => match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["version", value] => value.parse::<u32>().map_err(|err| SaveError::Parse {
+                    line: line + 1,
+                    message: err.to_string(),
+                })?,
+                _ => return Err(SaveError::Missing("a version")),
+            },
+            None => return Err(SaveError::Missing("a version")),
+        };
+
+        let mut raw = RawSave::new();
+        let mut section = String::new();
+        for (line, text) in lines {
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(name) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                section = name.to_string();
+                continue;
+            }
+            if section.is_empty() {
+                return Err(SaveError::Parse { line: line + 1, message: "entry outside a section".to_string() });
+            }
+
+            let mut parts = text.split_whitespace().map(str::to_string);
+            let key = parts.next().unwrap_or_default();
+            raw.entry(section.clone()).or_default().push((key, parts.collect()));
+        }
+
+        migrate(&mut raw, version)?;
+        SaveGame::from_raw(&raw)
+    }
+
+    fn from_raw(raw: &RawSave) -> Result<SaveGame, SaveError> {
+        let saved_at = parse_values::<u64>(raw, "meta", "saved_at")?[0];
+        let position = parse_values::<f32>(raw, "player", "position")?;
+        if position.len() != 2 {
+            return Err(SaveError::Invalid("player position"));
+        }
+
+        let pose = PlayerPose {
+            position: Vec2f::new(position[0], position[1]),
+            angle: parse_values::<f32>(raw, "player", "angle")?[0],
+            pitch: parse_values::<f32>(raw, "player", "pitch")?[0],
+        };
+
+        // Checked against the rows actually present before anything is
+        // allocated, so a bad size can't run the game out of memory
+        let size = parse_values::<usize>(raw, "map", "size")?;
+        let (width, height) = match size.as_slice() {
+            [width, height] if *width > 0 && *height > 0 => (*width, *height),
+            _ => return Err(SaveError::Invalid("map size")),
+        };
+        if width.checked_mul(height).is_none_or(|cells| cells > MAX_MAP_CELLS) {
+            return Err(SaveError::Invalid("map size, too large to load"));
+        }
+        let rows: Vec<&Vec<String>> = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "row")
+            .map(|(_, values)| values)
+            .collect();
+        if rows.len() != height {
+            return Err(SaveError::Invalid("number of map rows"));
+        }
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(SaveError::Invalid("map row length"));
+        }
+
+        let mut map = GameMap::new(width, height);
+        let policy = parse_values::<String>(raw, "map", "out_of_bounds")?;
+        map.out_of_bounds = OutOfBounds::from_name(&policy[0])
+            .ok_or(SaveError::Invalid("out_of_bounds policy"))?;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                map.set_tile(x, y, value.parse().map_err(|_| SaveError::Invalid("tile value"))?);
+            }
+        }
+
+        // The sky, ceilings, floors, portals, mirrors, thin walls, surfaces,
+        // switches and lights are optional; maps without them keep the
+        // defaults
+        if let Some(sky) = raw.get("map").into_iter().flatten().find(|(key, _)| key == "sky") {
+            let name = sky.1.first().ok_or(SaveError::Missing("a sky name"))?;
+            if !is_asset_name(name) {
+                return Err(SaveError::Invalid("sky name, which must not be a path"));
+            }
+            map.sky = name.clone();
+        }
+
+        let ceiling_rows: Vec<&Vec<String>> = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "ceiling_row")
+            .map(|(_, values)| values)
+            .collect();
+        if !ceiling_rows.is_empty() && ceiling_rows.len() != height {
+            return Err(SaveError::Invalid("number of ceiling rows"));
+        }
+        for (y, row) in ceiling_rows.iter().enumerate() {
+            if row.len() != width {
+                return Err(SaveError::Invalid("ceiling row length"));
+            }
+            for (x, value) in row.iter().enumerate() {
+                map.set_ceiling(x, y, value.parse().map_err(|_| SaveError::Invalid("ceiling value"))?);
+            }
+        }
+
+        let floor_rows: Vec<&Vec<String>> = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "floor_row")
+            .map(|(_, values)| values)
+            .collect();
+        if !floor_rows.is_empty() && floor_rows.len() != height {
+            return Err(SaveError::Invalid("number of floor rows"));
+        }
+        for (y, row) in floor_rows.iter().enumerate() {
+            if row.len() != width {
+                return Err(SaveError::Invalid("floor row length"));
+            }
+            for (x, value) in row.iter().enumerate() {
+                map.set_floor(x, y, value.parse().map_err(|_| SaveError::Invalid("floor value"))?);
+            }
+        }
+
+        let floors = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "floor");
+        for (_, values) in floors {
+            let invalid = || SaveError::Invalid("floor material");
+            let [floor_type, r, g, b, friction, speed] = values.as_slice() else { return Err(invalid()) };
+            let channel = |value: &String| value.parse::<u8>().map_err(|_| invalid());
+            let material = FloorMaterial {
+                color: [channel(r)?, channel(g)?, channel(b)?],
+                friction: friction.parse().map_err(|_| invalid())?,
+                speed: speed.parse().map_err(|_| invalid())?,
+            };
+            map.floor_materials.insert(floor_type.parse().map_err(|_| invalid())?, material);
+        }
+
+        let portals = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "portal");
+        for (_, values) in portals {
+            let (a, b) = match values.as_slice() {
+                [ax, ay, a_face, bx, by, b_face] => (parse_portal_face(ax, ay, a_face)?, parse_portal_face(bx, by, b_face)?),
+                _ => return Err(SaveError::Invalid("portal, which needs two faces")),
+            };
+            map.link_portals(a, b);
+        }
+
+        let mirrors = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "mirror");
+        for (_, values) in mirrors {
+            let invalid = || SaveError::Invalid("mirror definition");
+            let [wall_type, r, g, b, reflectivity] = values.as_slice() else { return Err(invalid()) };
+            let mirror = Mirror {
+                color: [
+                    r.parse().map_err(|_| invalid())?,
+                    g.parse().map_err(|_| invalid())?,
+                    b.parse().map_err(|_| invalid())?,
+                ],
+                reflectivity: reflectivity.parse().map_err(|_| invalid())?,
+            };
+            map.mirrors.insert(wall_type.parse().map_err(|_| invalid())?, mirror);
+        }
+
+        let segments = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "segment");
+        for (_, values) in segments {
+            let invalid = || SaveError::Invalid("thin wall segment");
+            let [x, y, ax, ay, bx, by, wall_type] = values.as_slice() else { return Err(invalid()) };
+            let coordinate = |value: &String| value.parse::<f32>().map_err(|_| invalid());
+            let segment = WallSegment::new(
+                Vec2f::new(coordinate(ax)?, coordinate(ay)?),
+                Vec2f::new(coordinate(bx)?, coordinate(by)?),
+                wall_type.parse().map_err(|_| invalid())?,
+            );
+            map.add_segment(x.parse().map_err(|_| invalid())?, y.parse().map_err(|_| invalid())?, segment);
+        }
+
+        let surfaces = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "surface");
+        for (_, values) in surfaces {
+            let invalid = || SaveError::Invalid("wall surface");
+            let [wall_type, texture, frames, frame_rate, scroll_u, scroll_v] = values.as_slice() else { return Err(invalid()) };
+            let number = |value: &String| value.parse::<f32>().map_err(|_| invalid());
+            let frames: u32 = frames.parse().map_err(|_| invalid())?;
+            if !is_asset_name(texture) || !(1..=MAX_SURFACE_FRAMES).contains(&frames) {
+                return Err(invalid());
+            }
+            let surface = WallSurface {
+                frames,
+                frame_rate: number(frame_rate)?,
+                scroll: Vec2f::new(number(scroll_u)?, number(scroll_v)?),
+                ..WallSurface::still(texture)
+            };
+            map.surfaces.insert(wall_type.parse().map_err(|_| invalid())?, surface);
+        }
+
+        let switches = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "switch");
+        for (_, values) in switches {
+            let invalid = || SaveError::Invalid("switch");
+            let [from, to] = values.as_slice() else { return Err(invalid()) };
+            map.switches.insert(from.parse().map_err(|_| invalid())?, to.parse().map_err(|_| invalid())?);
+        }
+
+        let lights = raw.get("map")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key == "light");
+        for (_, values) in lights {
+            let invalid = || SaveError::Invalid("light");
+            let [x, y, r, g, b, radius, intensity, flicker, enabled] = values.as_slice() else { return Err(invalid()) };
+            let number = |value: &String| value.parse::<f32>().map_err(|_| invalid());
+            let channel = |value: &String| value.parse::<u8>().map_err(|_| invalid());
+            map.lights.push(MapLight {
+                intensity: number(intensity)?,
+                flicker: number(flicker)?,
+                enabled: enabled.parse().map_err(|_| invalid())?,
+                ..MapLight::new(
+                    Vec2f::new(number(x)?, number(y)?),
+                    [channel(r)?, channel(g)?, channel(b)?],
+                    number(radius)?,
+                )
+            });
+        }
+
+        Ok(SaveGame {
+            saved_at,
+            pose,
+            move_speed: parse_values::<f32>(raw, "player", "move_speed")?[0],
+            rotation_speed: parse_values::<f32>(raw, "player", "rotation_speed")?[0],
+            mouse_sensitivity: parse_values::<f32>(raw, "player", "mouse_sensitivity")?[0],
+            map,
+        })
+    }
+}
+
+fn parse_portal_face(x: &str, y: &str, face: &str) -> Result<PortalFace, SaveError> {
+    let invalid = || SaveError::Invalid("portal face");
+    Ok(PortalFace::new(
+        x.parse().map_err(|_| invalid())?,
+        y.parse().map_err(|_| invalid())?,
+        Face::from_name(face).ok_or_else(invalid)?,
+    ))
+}
+
+// Returns at least one value, or an error naming the missing or unreadable field
+fn parse_values<T: std::str::FromStr>(raw: &RawSave, section: &str, key: &'static str) -> Result<Vec<T>, SaveError> {
+    let values = raw.get(section)
+        .and_then(|entries| entries.iter().find(|(name, _)| name == key))
+        .map(|(_, values)| values)
+        .filter(|values| !values.is_empty())
+        .ok_or(SaveError::Missing(key))?;
+
+    values.iter()
+        .map(|value| value.parse::<T>().map_err(|_| SaveError::Invalid(key)))
+        .collect()
+}
+
+pub fn slot_path(slot: u32) -> PathBuf {
+    Path::new(SAVE_DIR).join(format!("slot{}.sav", slot))
+}
+
+pub fn thumbnail_path(slot: u32) -> PathBuf {
+    Path::new(SAVE_DIR).join(format!("slot{}.ppm", slot))
+}
+
+pub fn save_to_slot(slot: u32, save: &SaveGame, canvas: &PixelCanvas) -> Result<(), SaveError> {
+    std::fs::create_dir_all(SAVE_DIR)?;
+    std::fs::write(slot_path(slot), save.to_text())?;
+    std::fs::write(thumbnail_path(slot), encode_thumbnail(canvas))?;
+    Ok(())
+}
+
+pub fn load_from_slot(slot: u32) -> Result<SaveGame, SaveError> {
+    let text = std::fs::read_to_string(slot_path(slot))?;
+    SaveGame::from_text(&text)
+}
+
+// Downscaled binary PPM, viewable in most image tools
+fn encode_thumbnail(canvas: &PixelCanvas) -> Vec<u8> {
+    let width = canvas.width / THUMBNAIL_SCALE;
+    let height = canvas.height / THUMBNAIL_SCALE;
+    let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = (((y * THUMBNAIL_SCALE) * canvas.width + x * THUMBNAIL_SCALE) * 4) as usize;
+            data.extend_from_slice(&canvas.pixels[index..index + 3]);
+        }
+    }
+    data
+}
+
+pub struct SaveSlotInfo {
+    pub slot: u32,
+    pub saved_at: u64,
+    pub position: Vec2f,
+    pub map_size: (usize, usize),
+    pub thumbnail: Option<PathBuf>,
+}
+
+pub fn list_save_slots() -> Vec<(u32, Result<SaveSlotInfo, SaveError>)> {
+    (0..SAVE_SLOT_COUNT)
+        .filter(|slot| slot_path(*slot).exists())
+        .map(|slot| {
+            let info = load_from_slot(slot).map(|save| SaveSlotInfo {
+                slot,
+                saved_at: save.saved_at,
+                position: save.pose.position,
+                map_size: (save.map.width, save.map.height),
+                thumbnail: Some(thumbnail_path(slot)).filter(|path| path.exists()),
+            });
+            (slot, info)
+        })
+        .collect()
+}
+
+fn log_save_slots(current: u32) {
+    let slots = list_save_slots();
+    if slots.is_empty() {
+        info!("No saves in {}/", SAVE_DIR);
+    }
+    for (slot, info) in slots {
+        let marker = if slot == current { ">" } else { " " };
+        match info {
+            Ok(info) => info!("{} Slot {}: saved at {} - pos ({:.1}, {:.1}), map {}x{}{}",
+                marker, info.slot, info.saved_at, info.position.x, info.position.y,
+                info.map_size.0, info.map_size.1,
+                if info.thumbnail.is_some() { ", thumbnail" } else { "" }),
+            Err(err) => info!("{} Slot {}: unreadable ({})", marker, slot, err),
+        }
+    }
+}
+
+fn handle_save_controls(
+    actions: Res<ActionState>,
+    mut slots: ResMut<SaveSlots>,
+    mut player: ResMut<Player>,
+    mut view: ResMut<PlayerView>,
+    mut map: ResMut<GameMap>,
+    mut messages: ResMut<HudMessages>,
+) {
+    if actions.just_pressed(Action::CycleSaveSlot) {
+        slots.current = (slots.current + 1) % SAVE_SLOT_COUNT;
+        info!("Save slot {} selected", slots.current);
+        messages.push(format!("Save slot {}", slots.current));
+        log_save_slots(slots.current);
+    }
+
+    if actions.just_pressed(Action::QuickLoad) {
+        match load_from_slot(slots.current) {
+            Ok(save) => {
+                save.apply(&mut player, &mut map);
+                view.snap_to(player.pose());
+                info!("Loaded slot {}", slots.current);
+                messages.push(format!("Loaded slot {}", slots.current));
+            }
+            Err(err) => {
+                warn!("Load from slot {} failed: {}", slots.current, err);
+                messages.push(format!("{{red}}Load failed:{{/}} {}", err));
+            }
+        }
+    }
+}
+
+fn quick_save(
+    actions: Res<ActionState>,
+    slots: Res<SaveSlots>,
+    player: Res<Player>,
+    map: Res<GameMap>,
+    canvas: Res<PixelCanvas>,
+    mut messages: ResMut<HudMessages>,
+) {
+    if !actions.just_pressed(Action::QuickSave) {
+        return;
+    }
+
+    let save = SaveGame::capture(&player, &map);
+    match save_to_slot(slots.current, &save, &canvas) {
+        Ok(()) => {
+            info!("Saved to slot {}", slots.current);
+            messages.push(format!("Saved to slot {}", slots.current));
+        }
+        Err(err) => {
+            warn!("Save to slot {} failed: {}", slots.current, err);
+            messages.push(format!("{{red}}Save failed:{{/}} {}", err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_save() -> SaveGame {
+        let mut map = GameMap::new(3, 2);
+        map.set_tile(0, 0, 1);
+        map.set_tile(2, 1, 4);
+        map.set_ceiling(1, 1, 2);
+        let mut player = Player::default();
+        player.set_pose(PlayerPose { position: Vec2f::new(1.5, 0.5), angle: 0.5, pitch: 0.1 });
+        SaveGame::capture(&player, &map)
+    }
+
+    #[test]
+    fn round_trip() {
+        let save = test_save();
+        let loaded = SaveGame::from_text(&save.to_text()).unwrap();
+        assert_eq!(loaded.pose, save.pose);
+        assert_eq!(loaded.map.tiles, save.map.tiles);
+        assert_eq!(loaded.map.ceilings, save.map.ceilings);
+        assert_eq!(loaded.map.out_of_bounds, save.map.out_of_bounds);
+    }
+
+    #[test]
+    fn truncated_rows_are_rejected() {
+        let text = save_text_without(&test_save().to_text(), |line| line.starts_with("row 0 0 4"));
+        assert!(matches!(SaveGame::from_text(&text), Err(SaveError::Invalid(_))));
+
+        let text = test_save().to_text().replace("row 0 0 4", "row 0 0");
+        assert!(matches!(SaveGame::from_text(&text), Err(SaveError::Invalid(_))));
+
+        let text = test_save().to_text().replace("size 3 2", "size 100000000 100000000");
+        assert!(matches!(SaveGame::from_text(&text), Err(SaveError::Invalid(_))));
+
+        let text = test_save().to_text().replace("size 3 2", &format!("size {} 2", usize::MAX));
+        assert!(matches!(SaveGame::from_text(&text), Err(SaveError::Invalid(_))));
+        // Absent fields are missing; present but unreadable ones are invalid
+        let text = save_text_without(&test_save().to_text(), |line| line.starts_with("pitch "));
+        let err = SaveGame::from_text(&text).err().unwrap();
+        assert!(matches!(err, SaveError::Missing("pitch")));
+        assert_eq!(err.to_string(), "save is missing pitch");
+        let text = test_save().to_text().replace("size 3 2", "size 3 x");
+        let err = SaveGame::from_text(&text).err().unwrap();
+        assert!(matches!(err, SaveError::Invalid("size")));
+        assert_eq!(err.to_string(), "save has an invalid size");
+    }
+
+    #[test]
+    fn surfaces_are_bounded() {
+        let mut save = test_save();
+        save.map.surfaces.insert(7, WallSurface::animated("screen", 4, 8.0));
+        let text = save.to_text();
+        assert_eq!(SaveGame::from_text(&text).unwrap().map.surfaces.get(&7).map(|surface| surface.frames), Some(4));
+        for bad in ["surface 7 screen 4000000000 ", "surface 7 screen 0 ", "surface 7 ../screen 4 ", "surface 7 a/b 4 "] {
+            let text = text.replace("surface 7 screen 4 ", bad);
+            assert!(matches!(SaveGame::from_text(&text), Err(SaveError::Invalid(_))), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn sky_names_stay_in_the_sky_directory() {
+        for name in ["../../secret", "a/b", "a\\b", ".."] {
+            let text = test_save().to_text().replace("sky default", &format!("sky {}", name));
+            assert!(matches!(SaveGame::from_text(&text), Err(SaveError::Invalid(_))), "{}", name);
+        }
+        let text = test_save().to_text().replace("sky default", "sky night");
+        assert_eq!(SaveGame::from_text(&text).unwrap().map.sky, "night");
+    }
+
+    #[test]
+    fn bad_versions_are_rejected() {
+        let text = test_save().to_text();
+        let current = format!("version {}", SAVE_VERSION);
+        assert!(matches!(SaveGame::from_text(&text.replace(&current, "version 99")), Err(SaveError::UnsupportedVersion(99))));
+        assert!(matches!(SaveGame::from_text(&text.replace(&current, "version 0")), Err(SaveError::UnsupportedVersion(0))));
+        assert!(matches!(SaveGame::from_text(&text.replace(&current, "version two")), Err(SaveError::Parse { line: 2, .. })));
+        assert!(matches!(SaveGame::from_text(&text.replace(SAVE_HEADER, "NOT_A_SAVE")), Err(SaveError::MissingHeader)));
+    }
+
+    fn save_text_without(text: &str, skip: impl Fn(&str) -> bool) -> String {
+        text.lines().filter(|line| !skip(line)).map(|line| format!("{}\n", line)).collect()
+    }
+}
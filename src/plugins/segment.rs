@@ -0,0 +1,93 @@
+use super::math::Vec2f;
+
+// How close the player may get to a thin wall, so the near plane never
+// ends up on the far side of it
+pub const SEGMENT_CLEARANCE: f32 = 0.1;
+
+// A wall that is a line inside a cell rather than the whole cell. Ends are
+// in cell-local coordinates, (0, 0) being the cell's north-west corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallSegment {
+    pub a: Vec2f,
+    pub b: Vec2f,
+    pub wall_type: u8,
+}
+
+// Where a ray crosses a segment
+pub struct SegmentHit {
+    pub distance: f32, // Ray parameter t
+    pub along: f32,    // Distance from a towards b, in cells
+    pub normal: Vec2f, // Unit length, facing back towards the ray
+}
+
+impl WallSegment {
+    pub fn new(a: Vec2f, b: Vec2f, wall_type: u8) -> Self {
+        Self { a, b, wall_type }
+    }
+
+    // A square post of half-width size around a point in the cell
+    pub fn pillar(center: Vec2f, size: f32, wall_type: u8) -> [WallSegment; 4] {
+        let corner = |x: f32, y: f32| Vec2f::new(center.x + x * size, center.y + y * size);
+        [
+            WallSegment::new(corner(-1.0, -1.0), corner(1.0, -1.0), wall_type),
+            WallSegment::new(corner(1.0, -1.0), corner(1.0, 1.0), wall_type),
+            WallSegment::new(corner(1.0, 1.0), corner(-1.0, 1.0), wall_type),
+            WallSegment::new(corner(-1.0, 1.0), corner(-1.0, -1.0), wall_type),
+        ]
+    }
+
+    // The same segment placed in the world at a cell's corner
+    pub fn in_cell(&self, cell_x: i32, cell_y: i32) -> WallSegment {
+        let corner = Vec2f::new(cell_x as f32, cell_y as f32);
+        WallSegment::new(corner + self.a, corner + self.b, self.wall_type)
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.b - self.a).length()
+    }
+
+    pub fn intersect_ray(&self, origin: Vec2f, direction: Vec2f) -> Option<SegmentHit> {
+        let edge = self.b - self.a;
+        let denominator = cross(direction, edge);
+        if denominator.abs() < 0.000001 {
+            return None; // Parallel
+        }
+
+        let to_start = self.a - origin;
+        let distance = cross(to_start, edge) / denominator;
+        let s = cross(to_start, direction) / denominator;
+        if distance < 0.0 || !(0.0..=1.0).contains(&s) {
+            return None;
+        }
+
+        let mut normal = Vec2f::new(-edge.y, edge.x).normalize();
+        if normal.dot(&direction) > 0.0 {
+            normal = normal * -1.0;
+        }
+        Some(SegmentHit {
+            distance,
+            along: s * edge.length(),
+            normal,
+        })
+    }
+
+    // Whether moving from one point to another passes through the segment
+    pub fn crosses(&self, from: Vec2f, to: Vec2f) -> bool {
+        self.intersect_ray(from, to - from)
+            .is_some_and(|hit| hit.distance <= 1.0)
+    }
+
+    pub fn distance_to(&self, point: Vec2f) -> f32 {
+        let edge = self.b - self.a;
+        let length_squared = edge.dot(&edge);
+        if length_squared < 0.000001 {
+            return (point - self.a).length();
+        }
+        let s = ((point - self.a).dot(&edge) / length_squared).clamp(0.0, 1.0);
+        (point - (self.a + edge * s)).length()
+    }
+}
+
+fn cross(a: Vec2f, b: Vec2f) -> f32 {
+    a.x * b.y - a.y * b.x
+}
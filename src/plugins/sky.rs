@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+use std::path::Path;
+use super::canvas::CanvasPass;
+use super::draw::{load_ppm, PixelImage};
+use super::map::GameMap;
+
+pub const SKY_DIR: &str = "assets/sky";
+pub const DEFAULT_SKY: &str = "default";
+const PANORAMA_SIZE: (u32, u32) = (1024, 200);
+const CLOUD_SIZE: (u32, u32) = (512, 120);
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Sky::load(DEFAULT_SKY))
+            .add_systems(Update, (load_map_sky, drift_clouds).chain().before(CanvasPass::World));
+    }
+}
+
+// A layer drawn over the panorama, e.g. clouds. Brightness doubles as
+// alpha, so black is clear sky.
+pub struct SkyLayer {
+    pub image: PixelImage,
+    pub parallax: f32, // How fast it scrolls as the player turns, relative to the panorama
+    pub drift: f32,    // Turns per second it moves on its own
+    pub offset: f32,   // Accumulated drift, in turns
+}
+
+// 360 degree panorama whose bottom row sits on the horizon
+#[derive(Resource)]
+pub struct Sky {
+    pub name: String,
+    pub panorama: PixelImage,
+    pub layers: Vec<SkyLayer>,
+}
+
+impl Sky {
+    // assets/sky/<name>.ppm and optionally <name>_clouds.ppm; missing
+    // files fall back to the built-in gradient and clouds
+    pub fn load(name: &str) -> Self {
+        let directory = Path::new(SKY_DIR);
+        let panorama = load_ppm(&directory.join(format!("{}.ppm", name)))
+            .unwrap_or_else(generate_panorama);
+        let clouds = load_ppm(&directory.join(format!("{}_clouds.ppm", name)))
+            .unwrap_or_else(generate_clouds);
+
+        Self {
+            name: name.to_string(),
+            panorama,
+            layers: vec![SkyLayer {
+                image: clouds,
+                parallax: 1.3,
+                drift: 0.004,
+                offset: 0.0,
+            }],
+        }
+    }
+
+    // Color for a view angle (radians) at a height above the horizon,
+    // where 0 is the horizon and 1 is the top of the panorama
+    pub fn sample(&self, angle: f32, height: f32) -> [u8; 4] {
+        let turns = angle / TAU;
+        let mut color = sample_wrapped(&self.panorama, turns, height);
+
+        for layer in &self.layers {
+            let cloud = sample_wrapped(&layer.image, turns * layer.parallax + layer.offset, height);
+            let alpha = cloud[0].max(cloud[1]).max(cloud[2]) as u32;
+            for channel in 0..3 {
+                color[channel] = ((color[channel] as u32 * (255 - alpha) + cloud[channel] as u32 * alpha) / 255) as u8;
+            }
+        }
+        color
+    }
+}
+
+fn sample_wrapped(image: &PixelImage, turns: f32, height: f32) -> [u8; 4] {
+    let u = turns.rem_euclid(1.0);
+    let x = ((u * image.width as f32) as u32).min(image.width - 1);
+    let y = ((1.0 - height.clamp(0.0, 1.0)) * (image.height - 1) as f32) as u32;
+    image.get_pixel(x, y)
+}
+
+// Deep blue overhead fading to haze, with a band of hills along the horizon
+fn generate_panorama() -> PixelImage {
+    let (width, height) = PANORAMA_SIZE;
+    let mut image = PixelImage::new(width, height);
+    for x in 0..width {
+        let turns = x as f32 / width as f32;
+        let hills = 0.12
+            + 0.05 * (turns * TAU * 3.0).sin()
+            + 0.03 * (turns * TAU * 7.0 + 1.3).sin()
+            + 0.015 * (turns * TAU * 17.0 + 0.4).sin();
+        for y in 0..height {
+            let above_horizon = 1.0 - y as f32 / (height - 1) as f32;
+            let color = if above_horizon < hills {
+                let shade = 0.6 + 0.4 * (above_horizon / hills);
+                [(70.0 * shade) as u8, (100.0 * shade) as u8, (90.0 * shade) as u8, 255]
+            } else {
+                let t = above_horizon.powf(0.7);
+                [
+                    (200.0 + (40.0 - 200.0) * t) as u8,
+                    (225.0 + (90.0 - 225.0) * t) as u8,
+                    (240.0 + (180.0 - 240.0) * t) as u8,
+                    255,
+                ]
+            };
+            image.set_pixel(x, y, color);
+        }
+    }
+    image
+}
+
+// Soft blobs that tile horizontally
+fn generate_clouds() -> PixelImage {
+    let (width, height) = CLOUD_SIZE;
+    let mut image = PixelImage::new(width, height);
+    for y in 0..height {
+        let above_horizon = 1.0 - y as f32 / (height - 1) as f32;
+        // Thin out towards the top and keep clear of the hills
+        let band = ((above_horizon - 0.35) * 3.0).clamp(0.0, 1.0) * ((1.0 - above_horizon) * 4.0).clamp(0.0, 1.0);
+        for x in 0..width {
+            let turns = x as f32 / width as f32 * TAU;
+            let v = y as f32 / height as f32 * TAU;
+            let density = (turns * 2.0).sin() * (v * 2.0 + turns).cos()
+                + 0.5 * (turns * 5.0 + 1.0).sin() * (v * 3.0).sin()
+                + 0.25 * (turns * 11.0).cos();
+            let amount = ((density - 0.3) * 1.5).clamp(0.0, 1.0) * band;
+            let value = (amount * 235.0) as u8;
+            image.set_pixel(x, y, [value, value, value, 255]);
+        }
+    }
+    image
+}
+
+fn load_map_sky(
+    map: Option<Res<GameMap>>,
+    mut sky: ResMut<Sky>,
+) {
+    let Some(map) = map else { return };
+    if map.is_changed() && map.sky != sky.name {
+        *sky = Sky::load(&map.sky);
+        info!("Sky: {}", map.sky);
+    }
+}
+
+fn drift_clouds(
+    mut sky: ResMut<Sky>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for layer in &mut sky.layers {
+        layer.offset = (layer.offset + layer.drift * dt).rem_euclid(1.0);
+    }
+}
@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::path::Path;
+use super::actions::{Action, ActionState};
+use super::canvas::CanvasPass;
+use super::debug::DebugOverlayAppExt;
+use super::draw::{is_asset_name, load_ppm, PixelImage};
+use super::hud::HudMessages;
+use super::map::GameMap;
+use super::math::Vec2f;
+use super::player::{Player, PlayerSystems, StepInput};
+use super::raycast::cast_ray;
+
+pub const TEXTURE_DIR: &str = "assets/textures";
+pub const USE_DISTANCE: f32 = 1.5; // How far away a switch can be used from
+pub const MAX_SURFACE_FRAMES: u32 = 256;
+const TEXTURE_SIZE: u32 = 32;
+
+pub struct SurfacePlugin;
+
+impl Plugin for SurfacePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(AnimationClock::default())
+            .insert_resource(SurfaceTextures::default())
+            .add_debug_page("Surfaces", surface_debug_page)
+            .add_systems(FixedUpdate, advance_clock.in_set(PlayerSystems::Simulate))
+            .add_systems(Update, (use_switches, load_surface_textures).chain().before(CanvasPass::World));
+    }
+}
+
+// Time as far as animations are concerned. It only moves with the fixed
+// simulation step, never the wall clock, so a replay or a screenshot of
+// the same tick always shows the same frame.
+#[derive(Resource, Default)]
+pub struct AnimationClock {
+    pub seconds: f32,
+    pub ticks: u64,
+}
+
+// How a wall type looks when it isn't a flat color
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallSurface {
+    pub texture: String, // Frames are assets/textures/<texture>_<n>.ppm, else built in
+    pub frames: u32,
+    pub frame_rate: f32, // Frames per second on the animation clock
+    pub scroll: Vec2f,   // Texture repeats per second along the wall (u) and down it (v)
+}
+
+impl WallSurface {
+    pub fn still(texture: &str) -> Self {
+        Self {
+            texture: texture.to_string(),
+            frames: 1,
+            frame_rate: 0.0,
+            scroll: Vec2f::new(0.0, 0.0),
+        }
+    }
+
+    pub fn animated(texture: &str, frames: u32, frame_rate: f32) -> Self {
+        Self {
+            frames,
+            frame_rate,
+            ..Self::still(texture)
+        }
+    }
+
+    pub fn frame_at(&self, seconds: f32) -> usize {
+        (seconds * self.frame_rate).max(0.0) as usize % self.frames.max(1) as usize
+    }
+
+    // Texture coordinates after scrolling, wrapped into 0..1
+    pub fn scrolled(&self, u: f32, v: f32, seconds: f32) -> (f32, f32) {
+        ((u + self.scroll.x * seconds).rem_euclid(1.0), (v + self.scroll.y * seconds).rem_euclid(1.0))
+    }
+}
+
+// Frames for every texture the map's surfaces use, loaded by name
+#[derive(Resource, Default)]
+pub struct SurfaceTextures {
+    textures: BTreeMap<String, Vec<PixelImage>>,
+}
+
+impl SurfaceTextures {
+    pub fn frame(&self, surface: &WallSurface, seconds: f32) -> Option<&PixelImage> {
+        let frames = self.textures.get(&surface.texture)?;
+        frames.get(surface.frame_at(seconds) % frames.len().max(1))
+    }
+
+    pub fn sample(image: &PixelImage, u: f32, v: f32) -> [u8; 4] {
+        let x = ((u * image.width as f32) as u32).min(image.width - 1);
+        let y = ((v * image.height as f32) as u32).min(image.height - 1);
+        image.get_pixel(x, y)
+    }
+
+    fn load(&mut self, surface: &WallSurface) {
+        if self.textures.get(&surface.texture).is_some_and(|frames| frames.len() == surface.frames as usize) {
+            return;
+        }
+
+        let directory = Path::new(TEXTURE_DIR);
+        let frames = (0..surface.frames.clamp(1, MAX_SURFACE_FRAMES))
+            .map(|frame| {
+                is_asset_name(&surface.texture)
+                    .then(|| load_ppm(&directory.join(format!("{}_{}.ppm", surface.texture, frame))))
+                    .flatten()
+                    .unwrap_or_else(|| generate_texture(&surface.texture, frame, surface.frames.max(1)))
+            })
+            .collect();
+        self.textures.insert(surface.texture.clone(), frames);
+    }
+}
+
+// Stand-ins for the textures the default map uses, so it animates without
+// any asset files
+fn generate_texture(name: &str, frame: u32, frames: u32) -> PixelImage {
+    let mut image = PixelImage::new(TEXTURE_SIZE, TEXTURE_SIZE);
+    let phase = frame as f32 / frames as f32 * std::f32::consts::TAU;
+    for y in 0..TEXTURE_SIZE {
+        for x in 0..TEXTURE_SIZE {
+            let (u, v) = (x as f32 / TEXTURE_SIZE as f32, y as f32 / TEXTURE_SIZE as f32);
+            let color = match name {
+                // Static-filled monitor inside a dark bezel
+                "screen" => {
+                    let bezel = !(3..TEXTURE_SIZE - 3).contains(&x) || !(5..TEXTURE_SIZE - 5).contains(&y);
+                    if bezel {
+                        [40, 40, 45]
+                    } else {
+                        let noise = hash(x, y, frame) as u8 / 2;
+                        let scanline = if y % 2 == 0 { 40 } else { 0 };
+                        [noise / 2, 90 + noise / 2 + scanline, 110 + noise / 2]
+                    }
+                }
+                "water" => {
+                    let wave = ((u * 3.0 + v * 2.0) * std::f32::consts::TAU + phase).sin() * 0.5 + 0.5;
+                    [(30.0 + 40.0 * wave) as u8, (80.0 + 60.0 * wave) as u8, (160.0 + 80.0 * wave) as u8]
+                }
+                "conveyor" => {
+                    let stripe = ((x + y) / 4) % 2 == 0;
+                    let rail = y < 3 || y >= TEXTURE_SIZE - 3;
+                    if rail { [90, 90, 90] } else if stripe { [200, 170, 30] } else { [40, 40, 40] }
+                }
+                "switch_off" | "switch_on" => {
+                    let on = name == "switch_on";
+                    let plate = (10..22).contains(&x) && (8..24).contains(&y);
+                    let lever = (14..18).contains(&x) && if on { (8..16).contains(&y) } else { (16..24).contains(&y) };
+                    if lever {
+                        if on { [60, 220, 80] } else { [220, 50, 40] }
+                    } else if plate {
+                        [150, 150, 160]
+                    } else {
+                        [100, 100, 105]
+                    }
+                }
+                _ => {
+                    let checker = (x / 8 + y / 8) % 2 == 0;
+                    if checker { [255, 0, 255] } else { [30, 30, 30] }
+                }
+            };
+            image.set_pixel(x, y, [color[0], color[1], color[2], 255]);
+        }
+    }
+    image
+}
+
+fn hash(x: u32, y: u32, seed: u32) -> u32 {
+    let n = x.wrapping_mul(374761393) ^ y.wrapping_mul(668265263) ^ seed.wrapping_mul(2246822519);
+    let n = (n ^ (n >> 13)).wrapping_mul(1274126177);
+    (n ^ (n >> 16)) & 0xff
+}
+
+fn advance_clock(
+    mut clock: ResMut<AnimationClock>,
+    step: Res<StepInput>,
+) {
+    clock.seconds += step.dt;
+    clock.ticks += 1;
+}
+
+fn load_surface_textures(
+    map: Option<Res<GameMap>>,
+    mut textures: ResMut<SurfaceTextures>,
+) {
+    let Some(map) = map else { return };
+    if map.is_changed() {
+        for surface in map.surfaces.values() {
+            textures.load(surface);
+        }
+    }
+}
+
+// Using a switch swaps its wall type for its partner, e.g. off for on
+fn use_switches(
+    actions: Res<ActionState>,
+    player: Res<Player>,
+    map: Option<ResMut<GameMap>>,
+    mut messages: ResMut<HudMessages>,
+) {
+    let Some(mut map) = map else { return };
+    if !actions.just_pressed(Action::Use) || map.switches.is_empty() {
+        return;
+    }
+
+    let Some(hit) = cast_ray(&player.position, player.direction, &map, USE_DISTANCE) else { return };
+    let Some(&next) = map.switches.get(&hit.wall_type).filter(|_| !hit.thin) else { return };
+    let (x, y) = map.wrap_cell(hit.map_x, hit.map_y);
+    if map.in_bounds(x, y) {
+        map.set_tile(x as usize, y as usize, next);
+        messages.push(format!("Switch at ({}, {}) flipped", x, y));
+    }
+}
+
+fn surface_debug_page(world: &World) -> Vec<String> {
+    let clock = world.resource::<AnimationClock>();
+    let textures = world.resource::<SurfaceTextures>();
+    let mut lines = vec![format!("Clock: {:.2}s ({} ticks)", clock.seconds, clock.ticks)];
+    for (name, frames) in &textures.textures {
+        lines.push(format!("{}: {} frames", name, frames.len()));
+    }
+    lines
+}
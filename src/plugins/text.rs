@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+use std::path::Path;
+use super::canvas::PixelCanvas;
+use super::font_data::FONT_6X10_GLYPHS;
+
+// Largest offset, advance or line height a font file may give. Well past any
+// real font, and small enough that layout sums stay far from overflowing.
+const MAX_FONT_METRIC: i32 = 4096;
+
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub advance: i32,
+    pub coverage: Vec<u8>, // width * height, 0 = empty, 255 = solid
+}
+
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    pub line_height: u32,
+    glyphs: HashMap<char, Glyph>,
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    UnknownFormat,
+    Truncated,
+    Invalid(String),
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FontError::Io(err) => write!(f, "{}", err),
+            FontError::UnknownFormat => write!(f, "not a PSF or BMFont file"),
+            FontError::Truncated => write!(f, "font data is truncated"),
+            FontError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<std::io::Error> for FontError {
+    fn from(err: std::io::Error) -> Self {
+        FontError::Io(err)
+    }
+}
+
+impl BitmapFont {
+    // The bundled 6x10 font, always available
+    pub fn builtin() -> Self {
+        let mut glyphs = HashMap::new();
+        for (index, rows) in FONT_6X10_GLYPHS.iter().enumerate() {
+            let coverage = rows.iter()
+                .flat_map(|row| (0..6).map(move |bit| if row & (0x20 >> bit) != 0 { 255 } else { 0 }))
+                .collect();
+            glyphs.insert(char::from(0x20 + index as u8), Glyph {
+                width: 6,
+                height: 10,
+                x_offset: 0,
+                y_offset: 0,
+                advance: 6,
+                coverage,
+            });
+        }
+
+        Self {
+            line_height: 10,
+            glyphs,
+        }
+    }
+
+    // Picks the loader from the file extension: .psf or .fnt (BMFont text)
+    pub fn load(path: &Path) -> Result<Self, FontError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("psf") | Some("psfu") => Self::from_psf(&std::fs::read(path)?),
+            Some("fnt") => {
+                let text = std::fs::read_to_string(path)?;
+                Self::from_bmfont(&text, path.parent().unwrap_or(Path::new(".")))
+            }
+            _ => Err(FontError::UnknownFormat),
+        }
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    // Linux console fonts, PSF version 1 and 2, with optional unicode table
+    pub fn from_psf(data: &[u8]) -> Result<Self, FontError> {
+        let read_u32 = |offset: usize| -> Result<u32, FontError> {
+            data.get(offset..offset + 4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .ok_or(FontError::Truncated)
+        };
+
+        let (header_size, count, bytes_per_glyph, width, height, has_table, version) =
+            if data.starts_with(&[0x36, 0x04]) {
+                let mode = *data.get(2).ok_or(FontError::Truncated)?;
+                let height = *data.get(3).ok_or(FontError::Truncated)? as u32;
+                let count = if mode & 0x01 != 0 { 512 } else { 256 };
+                (4, count, height, 8, height, mode & 0x02 != 0, 1)
+            } else if data.starts_with(&[0x72, 0xb5, 0x4a, 0x86]) {
+                let header_size = read_u32(8)? as usize;
+                let flags = read_u32(12)?;
+                (header_size, read_u32(16)?, read_u32(20)?, read_u32(28)?, read_u32(24)?, flags & 0x01 != 0, 2)
+            } else {
+                return Err(FontError::UnknownFormat);
+            };
+
+        let row_bytes = width.div_ceil(8) as usize;
+        if width == 0 || height == 0 || (row_bytes * height as usize) > bytes_per_glyph as usize {
+            return Err(FontError::Invalid(format!("bad glyph size {}x{}", width, height)));
+        }
+
+        // Header values are untrusted, so the glyph table size is checked
+        // rather than assumed to fit
+        let glyph_data_end = (count as usize)
+            .checked_mul(bytes_per_glyph as usize)
+            .and_then(|size| size.checked_add(header_size))
+            .ok_or(FontError::Truncated)?;
+        if data.len() < glyph_data_end {
+            return Err(FontError::Truncated);
+        }
+
+        let decode_glyph = |index: usize| -> Glyph {
+            let start = header_size + index * bytes_per_glyph as usize;
+            let mut coverage = Vec::with_capacity(width as usize * height as usize);
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    let byte = data[start + y * row_bytes + x / 8];
+                    coverage.push(if byte & (0x80 >> (x % 8)) != 0 { 255 } else { 0 });
+                }
+            }
+            Glyph {
+                width,
+                height,
+                x_offset: 0,
+                y_offset: 0,
+                advance: width as i32,
+                coverage,
+            }
+        };
+
+        let mut glyphs = HashMap::new();
+        if has_table {
+            for (index, chars) in parse_psf_unicode_table(&data[glyph_data_end..], count as usize, version)
+                .into_iter()
+                .enumerate()
+            {
+                for c in chars {
+                    glyphs.insert(c, decode_glyph(index));
+                }
+            }
+        } else {
+            for index in 0..count.min(256) as usize {
+                glyphs.insert(char::from(index as u8), decode_glyph(index));
+            }
+        }
+
+        Ok(Self {
+            line_height: height,
+            glyphs,
+        })
+    }
+
+    // AngelCode BMFont text descriptor. Pages must be uncompressed TGA.
+    pub fn from_bmfont(text: &str, directory: &Path) -> Result<Self, FontError> {
+        let mut line_height = 0;
+        let mut pages: HashMap<u32, TgaImage> = HashMap::new();
+        let mut glyphs = HashMap::new();
+
+        for line in text.lines() {
+            let mut parts = line.split_whitespace();
+            let Some(tag) = parts.next() else { continue };
+            let fields: HashMap<&str, &str> = parts
+                .filter_map(|part| part.split_once('='))
+                .map(|(key, value)| (key, value.trim_matches('"')))
+                .collect();
+            let number = |key: &str| -> Result<i32, FontError> {
+                fields.get(key)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| FontError::Invalid(format!("'{}' line is missing {}", tag, key)))
+            };
+            let unsigned = |key: &str| -> Result<u32, FontError> {
+                u32::try_from(number(key)?)
+                    .map_err(|_| FontError::Invalid(format!("'{}' line has a negative {}", tag, key)))
+            };
+            let metric = |key: &str| -> Result<i32, FontError> {
+                Some(number(key)?)
+                    .filter(|value| value.abs() <= MAX_FONT_METRIC)
+                    .ok_or_else(|| FontError::Invalid(format!("'{}' line has an out of range {}", tag, key)))
+            };
+
+            match tag {
+                "common" => line_height = metric("lineHeight").and_then(|_| unsigned("lineHeight"))?,
+                "page" => {
+                    let file = fields.get("file")
+                        .ok_or_else(|| FontError::Invalid("page without a file".to_string()))?;
+                    let image = TgaImage::decode(&std::fs::read(directory.join(file))?)?;
+                    pages.insert(unsigned("id")?, image);
+                }
+                "char" => {
+                    let page = pages.get(&unsigned("page")?)
+                        .ok_or_else(|| FontError::Invalid("char refers to a missing page".to_string()))?;
+                    let Some(c) = char::from_u32(unsigned("id")?) else { continue };
+                    let (x, y) = (unsigned("x")?, unsigned("y")?);
+                    let (width, height) = (unsigned("width")?, unsigned("height")?);
+                    let fits = |start: u32, size: u32, limit: u32| start.checked_add(size).is_some_and(|end| end <= limit);
+                    if !fits(x, width, page.width) || !fits(y, height, page.height) {
+                        return Err(FontError::Invalid(format!("char {} lies outside its page", c as u32)));
+                    }
+
+                    let size = width.checked_mul(height)
+                        .ok_or_else(|| FontError::Invalid(format!("char {} is too large", c as u32)))?;
+                    let mut coverage = Vec::with_capacity(size as usize);
+                    for gy in 0..height {
+                        for gx in 0..width {
+                            coverage.push(page.coverage(x + gx, y + gy));
+                        }
+                    }
+                    glyphs.insert(c, Glyph {
+                        width,
+                        height,
+                        x_offset: metric("xoffset")?,
+                        y_offset: metric("yoffset")?,
+                        advance: metric("xadvance")?,
+                        coverage,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if glyphs.is_empty() {
+            return Err(FontError::Invalid("font has no characters".to_string()));
+        }
+
+        Ok(Self {
+            line_height,
+            glyphs,
+        })
+    }
+}
+
+// Each glyph's entry lists the characters it draws, ending at a terminator.
+// Combining sequences (after the sequence marker) are skipped.
+fn parse_psf_unicode_table(table: &[u8], count: usize, version: u8) -> Vec<Vec<char>> {
+    let mut result = Vec::with_capacity(count);
+    let mut offset = 0;
+
+    while result.len() < count && offset < table.len() {
+        let mut chars = Vec::new();
+        let mut in_sequence = false;
+
+        if version == 1 {
+            while offset + 1 < table.len() {
+                let value = u16::from_le_bytes([table[offset], table[offset + 1]]);
+                offset += 2;
+                match value {
+                    0xffff => break,
+                    0xfffe => in_sequence = true,
+                    _ if !in_sequence => chars.extend(char::from_u32(value as u32)),
+                    _ => {}
+                }
+            }
+        } else {
+            let end = table[offset..].iter().position(|&b| b == 0xff).map_or(table.len(), |p| offset + p);
+            let entry = &table[offset..end];
+            let singles = entry.split(|&b| b == 0xfe).next().unwrap_or(&[]);
+            if let Ok(text) = std::str::from_utf8(singles) {
+                chars.extend(text.chars());
+            }
+            offset = end + 1;
+        }
+
+        result.push(chars);
+    }
+    result
+}
+
+struct TgaImage {
+    width: u32,
+    height: u32,
+    channels: usize,
+    pixels: Vec<u8>, // Top-down, BGR(A) or grayscale
+}
+
+impl TgaImage {
+    // Uncompressed true-color (type 2) or grayscale (type 3) only
+    fn decode(data: &[u8]) -> Result<Self, FontError> {
+        if data.len() < 18 {
+            return Err(FontError::Truncated);
+        }
+
+        let id_length = data[0] as usize;
+        let image_type = data[2];
+        let width = u16::from_le_bytes([data[12], data[13]]) as u32;
+        let height = u16::from_le_bytes([data[14], data[15]]) as u32;
+        let channels = (data[16] / 8) as usize;
+        let top_down = data[17] & 0x20 != 0;
+
+        if !matches!((image_type, channels), (2, 3) | (2, 4) | (3, 1)) {
+            return Err(FontError::Invalid("font pages must be uncompressed TGA".to_string()));
+        }
+
+        let start = 18 + id_length;
+        let row_size = width as usize * channels;
+        let size = row_size * height as usize;
+        let source = data.get(start..start + size).ok_or(FontError::Truncated)?;
+
+        let mut pixels = Vec::with_capacity(size);
+        for y in 0..height as usize {
+            let row = if top_down { y } else { height as usize - 1 - y };
+            pixels.extend_from_slice(&source[row * row_size..(row + 1) * row_size]);
+        }
+
+        Ok(Self { width, height, channels, pixels })
+    }
+
+    // Alpha for glyphs on a transparent page, brightness for opaque pages
+    fn coverage(&self, x: u32, y: u32) -> u8 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+
+        let index = (y * self.width + x) as usize * self.channels;
+        let pixel = &self.pixels[index..index + self.channels];
+        match pixel {
+            [gray] => *gray,
+            [b, g, r] => *r.max(g).max(b),
+            [b, g, r, a] => (*a).min(*r.max(g).max(b)),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    pub color: [u8; 4],
+    pub shadow: Option<[u8; 4]>,
+    pub align: TextAlign,
+    pub max_width: Option<u32>, // Wrap at spaces beyond this many pixels
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: [255, 255, 255, 255],
+            shadow: Some([0, 0, 0, 200]),
+            align: TextAlign::Left,
+            max_width: None,
+        }
+    }
+}
+
+// Markup: {red} {green} {blue} {yellow} {cyan} {magenta} {white} {gray}
+// {#rrggbb} change the color, {/} restores the style color, {{ is a brace
+fn parse_markup(text: &str, default_color: [u8; 4]) -> Vec<(char, [u8; 4])> {
+    let mut result = Vec::with_capacity(text.len());
+    let mut color = default_color;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '{' {
+            result.push((c, color));
+            continue;
+        }
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            result.push(('{', color));
+            continue;
+        }
+
+        let tag: String = chars.by_ref().take_while(|&c| c != '}').collect();
+        color = match tag.as_str() {
+            "/" => default_color,
+            "red" => [255, 80, 80, default_color[3]],
+            "green" => [80, 255, 80, default_color[3]],
+            "blue" => [100, 140, 255, default_color[3]],
+            "yellow" => [255, 255, 80, default_color[3]],
+            "cyan" => [80, 255, 255, default_color[3]],
+            "magenta" => [255, 80, 255, default_color[3]],
+            "white" => [255, 255, 255, default_color[3]],
+            "gray" => [160, 160, 160, default_color[3]],
+            hex if hex.len() == 7 && hex.starts_with('#') => {
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+                match (channel(1), channel(3), channel(5)) {
+                    (Some(r), Some(g), Some(b)) => [r, g, b, default_color[3]],
+                    _ => color,
+                }
+            }
+            _ => color,
+        };
+    }
+    result
+}
+
+impl BitmapFont {
+    fn advance(&self, c: char) -> i32 {
+        self.glyph(c).map_or(0, |glyph| glyph.advance)
+    }
+
+    fn line_width(&self, line: &[(char, [u8; 4])]) -> i32 {
+        line.iter().fold(0, |width: i32, (c, _)| width.saturating_add(self.advance(*c)))
+    }
+
+    // Splits marked-up text into lines at newlines and, with a max width,
+    // at the last space that still fits
+    fn layout(&self, text: &str, style: &TextStyle) -> Vec<Vec<(char, [u8; 4])>> {
+        let mut lines = Vec::new();
+
+        for paragraph in parse_markup(text, style.color).split(|(c, _)| *c == '\n') {
+            let Some(max_width) = style.max_width else {
+                lines.push(paragraph.to_vec());
+                continue;
+            };
+
+            let mut line: Vec<(char, [u8; 4])> = Vec::new();
+            let mut width = 0;
+            for &(c, color) in paragraph {
+                let advance = self.advance(c);
+                if width.saturating_add(advance) > max_width as i32 && !line.is_empty() {
+                    let break_at = line.iter().rposition(|(c, _)| *c == ' ');
+                    let rest = match break_at {
+                        Some(index) => {
+                            let rest = line.split_off(index + 1);
+                            line.pop();
+                            rest
+                        }
+                        None => Vec::new(),
+                    };
+                    lines.push(std::mem::replace(&mut line, rest));
+                    width = self.line_width(&line);
+                }
+                line.push((c, color));
+                width = width.saturating_add(advance);
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    pub fn measure(&self, text: &str, style: &TextStyle) -> (u32, u32) {
+        let lines = self.layout(text, style);
+        let width = lines.iter().map(|line| self.line_width(line)).max().unwrap_or(0);
+        (width.max(0) as u32, (lines.len() as u32).saturating_mul(self.line_height))
+    }
+}
+
+impl PixelCanvas {
+    // x is the left edge, center or right edge depending on the alignment.
+    // Returns the height of the drawn block.
+    pub fn draw_text(&mut self, font: &BitmapFont, text: &str, x: i32, y: i32, style: &TextStyle) -> u32 {
+        let lines = font.layout(text, style);
+
+        for (row, line) in lines.iter().enumerate() {
+            let width = font.line_width(line);
+            let mut pen_x = match style.align {
+                TextAlign::Left => x,
+                TextAlign::Center => x.saturating_sub(width / 2),
+                TextAlign::Right => x.saturating_sub(width),
+            };
+            let pen_y = y.saturating_add((row as u32).saturating_mul(font.line_height).min(i32::MAX as u32) as i32);
+
+            for &(c, color) in line {
+                let Some(glyph) = font.glyph(c) else { continue };
+                if let Some(shadow) = style.shadow {
+                    self.draw_glyph(glyph, pen_x.saturating_add(1), pen_y.saturating_add(1), shadow);
+                }
+                self.draw_glyph(glyph, pen_x, pen_y, color);
+                pen_x = pen_x.saturating_add(glyph.advance);
+            }
+        }
+
+        (lines.len() as u32).saturating_mul(font.line_height)
+    }
+
+    fn draw_glyph(&mut self, glyph: &Glyph, x: i32, y: i32, color: [u8; 4]) {
+        for gy in 0..glyph.height {
+            for gx in 0..glyph.width {
+                let coverage = glyph.coverage[(gy * glyph.width + gx) as usize];
+                if coverage == 0 {
+                    continue;
+                }
+
+                let alpha = (color[3] as u32 * coverage as u32 / 255) as u8;
+                self.plot(
+                    x.saturating_add(glyph.x_offset).saturating_add(gx as i32),
+                    y.saturating_add(glyph.y_offset).saturating_add(gy as i32),
+                    [color[0], color[1], color[2], alpha],
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn psf2(count: u32, bytes_per_glyph: u32, width: u32, height: u32) -> Vec<u8> {
+        let mut data = vec![0x72, 0xb5, 0x4a, 0x86];
+        for value in [0, 32, 0, count, bytes_per_glyph, height, width] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn psf2_header_sizes_are_checked() {
+        let mut data = psf2(2, 8, 8, 8);
+        data.extend_from_slice(&[0xff; 16]);
+        let font = BitmapFont::from_psf(&data).unwrap();
+        assert_eq!(font.line_height, 8);
+
+        assert!(matches!(BitmapFont::from_psf(&psf2(u32::MAX, u32::MAX, 8, 8)), Err(FontError::Truncated)));
+        assert!(matches!(BitmapFont::from_psf(&psf2(2, 8, 8, 8)), Err(FontError::Truncated)));
+        assert!(matches!(BitmapFont::from_psf(&psf2(2, 1, 8, 8)), Err(FontError::Invalid(_))));
+    }
+
+    #[test]
+    fn bmfont_metrics_are_bounded() {
+        let directory = std::env::temp_dir().join(format!("raycaster_font_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        // 2x2 grayscale TGA, stored top-down
+        let mut page = vec![0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, 8, 0x20];
+        page.extend_from_slice(&[255; 4]);
+        std::fs::write(directory.join("page.tga"), page).unwrap();
+
+        let font = |char_line: &str| {
+            let text = format!("common lineHeight=10\npage id=0 file=\"page.tga\"\n{}\n", char_line);
+            BitmapFont::from_bmfont(&text, &directory)
+        };
+        let glyph = "char id=65 x=0 y=0 width=2 height=2 page=0";
+        let font_ok = font(&format!("{} xoffset=-1 yoffset=0 xadvance=3", glyph)).unwrap();
+        assert_eq!(font_ok.measure("AA", &TextStyle::default()).0, 6);
+
+        for metrics in ["xoffset=2147483647 yoffset=0 xadvance=3", "xoffset=0 yoffset=-5000 xadvance=3",
+                        "xoffset=0 yoffset=0 xadvance=2147483647", "xoffset=0 yoffset=0 xadvance=-4097"] {
+            assert!(matches!(font(&format!("{} {}", glyph, metrics)), Err(FontError::Invalid(_))), "{}", metrics);
+        }
+        let _ = std::fs::remove_dir_all(&directory);
+
+        // Layout of the largest allowed values saturates instead of overflowing
+        let wide = BitmapFont {
+            line_height: MAX_FONT_METRIC as u32,
+            glyphs: HashMap::from([('W', Glyph {
+                width: 1,
+                height: 1,
+                x_offset: MAX_FONT_METRIC,
+                y_offset: MAX_FONT_METRIC,
+                advance: MAX_FONT_METRIC,
+                coverage: vec![255],
+            })]),
+        };
+        let text = "W".repeat(1 << 20);
+        assert_eq!(wide.measure(&text, &TextStyle::default()).0, i32::MAX as u32);
+        let mut canvas = PixelCanvas::new(4, 4);
+        canvas.draw_text(&wide, &text, i32::MAX - 10, i32::MAX - 10, &TextStyle::default());
+    }
+}